@@ -0,0 +1,153 @@
+//! authentication of the operational endpoints
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+
+use crate::config::{AdminConfig, Config};
+
+/// guard the routes of `admin { routes }`, others pass through
+pub async fn guard_routes(State(config): State<Config>, request: Request, next: Next) -> Response {
+    if config.admin.guards(request.uri().path()) {
+        if let Some(response) = reject(&config.admin, request.headers()) {
+            return response;
+        }
+    }
+    next.run(request).await
+}
+
+/// guard the admin api whatever `admin { routes }` is
+pub async fn require_admin(State(config): State<Config>, request: Request, next: Next) -> Response {
+    if let Some(response) = reject(&config.admin, request.headers()) {
+        return response;
+    }
+    next.run(request).await
+}
+
+/// the error response unless the request has the bearer token or the basic credentials
+/// of `admin { }`
+fn reject(admin: &AdminConfig, headers: &HeaderMap) -> Option<Response> {
+    if !admin.has_credentials() {
+        return Some(
+            (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "admin credentials are not configured" })),
+            )
+                .into_response(),
+        );
+    }
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|i| i.to_str().ok())
+        .unwrap_or_default();
+    let bearer_ok = match (&admin.token, authorization.strip_prefix("Bearer ")) {
+        (Some(token), Some(provided)) => constant_time_eq(provided.as_bytes(), token.as_bytes()),
+        _ => false,
+    };
+    let basic_ok = match (&admin.basic_auth, authorization.strip_prefix("Basic ")) {
+        (Some((user, password)), Some(provided)) => STANDARD
+            .decode(provided.trim())
+            .is_ok_and(|i| constant_time_eq(&i, format!("{user}:{password}").as_bytes())),
+        _ => false,
+    };
+    if bearer_ok || basic_ok {
+        return None;
+    }
+
+    let mut response = (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({ "error": "invalid or missing admin credentials" })),
+    )
+        .into_response();
+    if admin.basic_auth.is_some() {
+        // let browsers prompt for the status page
+        response.headers_mut().append(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_static(r#"Basic realm="mine-stats""#),
+        );
+    }
+    if admin.token.is_some() {
+        response
+            .headers_mut()
+            .append(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    Some(response)
+}
+
+/// compare secrets without leaking the matched prefix length through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(admin: &AdminConfig, authorization: Option<&str>) -> Option<StatusCode> {
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = authorization {
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(authorization).unwrap(),
+            );
+        }
+        reject(admin, &headers).map(|i| i.status())
+    }
+
+    #[test]
+    fn test_reject() {
+        // nothing passes without credentials
+        let admin = AdminConfig::default();
+        assert_eq!(status(&admin, None), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status(&admin, Some("Bearer ")), Some(StatusCode::FORBIDDEN));
+
+        let admin = AdminConfig {
+            token: Some("secret".to_string()),
+            basic_auth: Some(("admin".to_string(), "hunter2".to_string())),
+            ..Default::default()
+        };
+        let basic = |credentials: &str| format!("Basic {}", STANDARD.encode(credentials));
+        assert_eq!(status(&admin, Some("Bearer secret")), None);
+        assert_eq!(status(&admin, Some(&basic("admin:hunter2"))), None);
+        for authorization in [
+            None,
+            Some("Bearer secre"),
+            Some("Bearer secret2"),
+            Some("bearer secret"),
+            Some("Basic not-base64"),
+            Some(basic("admin:hunter").as_str()),
+            Some(basic("root:hunter2").as_str()),
+            // the token is not a basic password
+            Some(basic("admin:secret").as_str()),
+        ] {
+            assert_eq!(
+                status(&admin, authorization),
+                Some(StatusCode::UNAUTHORIZED),
+                "{authorization:?}"
+            );
+        }
+
+        // both ways to log in are offered
+        let response = reject(&admin, &HeaderMap::new()).unwrap();
+        let challenges: Vec<_> = response
+            .headers()
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .collect();
+        assert_eq!(challenges, [r#"Basic realm="mine-stats""#, "Bearer"]);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secre"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
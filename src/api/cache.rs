@@ -1,12 +1,23 @@
 //! cache api
 
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::{IntoResponse, Json},
 };
+use serde::Deserialize;
 use serde_json::json;
 
-use crate::cache::{list_keys, SharedCache};
+use crate::{
+    cache::{self, list_keys, SharedCache},
+    config::Config,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct KeyParams {
+    /// full key as listed by `/cache/entries`
+    key: String,
+}
 
 /// list all cached keys
 pub async fn list_keys_api(State(cache): State<SharedCache>) -> impl IntoResponse {
@@ -16,3 +27,89 @@ pub async fn list_keys_api(State(cache): State<SharedCache>) -> impl IntoRespons
         "count": keys.len(),
     }))
 }
+
+/// cache hit, miss and eviction counters
+pub async fn get_stats_api(
+    State(config): State<Config>,
+    State(cache): State<SharedCache>,
+) -> impl IntoResponse {
+    let cache = cache.read().unwrap();
+    let stats = cache.stats();
+    Json(json!({
+        "entries": stats.entries,
+        "max_entries": cache.max_entries(),
+        "ttl_secs": config.cache.ttl.as_secs(),
+        "stale_ttl_secs": cache.stale_ttl().as_secs(),
+        "hits": stats.hits,
+        "misses": stats.misses,
+        "evictions": stats.evictions,
+    }))
+}
+
+/// age, size and type of all cached entries
+pub async fn list_entries_api(State(cache): State<SharedCache>) -> impl IntoResponse {
+    let entries = cache.read().unwrap().entries();
+    let items = entries
+        .iter()
+        .map(|i| {
+            json!({
+                "key": i.key,
+                "type": i.data_type,
+                "age_secs": i.age.as_secs(),
+                "ttl_secs": i.ttl.as_secs(),
+                "size": i.size,
+                "refreshable": i.refreshable,
+            })
+        })
+        .collect::<Vec<_>>();
+    Json(json!({
+        "items": items,
+        "count": items.len(),
+    }))
+}
+
+/// `DELETE /cache/entries?key=`
+pub async fn delete_entry_api(
+    State(cache): State<SharedCache>,
+    Query(params): Query<KeyParams>,
+) -> impl IntoResponse {
+    let status = if cache.write().unwrap().remove(&params.key) {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    };
+    (status, Json(json!({ "key": params.key })))
+}
+
+/// `DELETE /cache/users/:user`, all data types of a user
+pub async fn purge_user_api(
+    State(cache): State<SharedCache>,
+    Path(user): Path<String>,
+) -> impl IntoResponse {
+    let removed = cache.write().unwrap().purge_user(&user);
+    Json(json!({
+        "user": user,
+        "removed": removed,
+    }))
+}
+
+/// `POST /cache/refresh?key=`, fetch the entry from github now
+pub async fn refresh_entry_api(
+    State(cache): State<SharedCache>,
+    Query(params): Query<KeyParams>,
+) -> impl IntoResponse {
+    match cache::refresh(&cache, &params.key).await {
+        Some(Ok(())) => (StatusCode::OK, Json(json!({ "key": params.key }))),
+        Some(Err(e)) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "key": params.key, "error": e })),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "key": params.key,
+                "error": "not requested since start, nothing to refresh",
+            })),
+        ),
+    }
+}
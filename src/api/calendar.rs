@@ -0,0 +1,100 @@
+//! github contribution calendar api
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, RawQuery, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+
+use super::{check_user, parse_query, CardResponse, UserParams};
+use crate::{
+    cache::{self, SharedCache},
+    cards::{form_calendar_card, CalendarCardOptions},
+    config::{CacheKind, Config, Themes},
+    error::AppError,
+    github::{calendar::get_contribution_calendar, GithubClient},
+};
+
+/// get user contribution calendar from github, and return a svg heatmap
+/// cache enabled
+#[allow(clippy::too_many_arguments)]
+pub async fn get_calendar_svg(
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<CalendarCardOptions>, QueryRejection>,
+    State(config): State<Config>,
+    State(themes): State<Themes>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        // the theme is part of the query that failed
+        Err(e) => return e.with_theme(themes.find(None::<&str>)).into_response(),
+    };
+    let output = params.output();
+    let numbers = params.numbers();
+    let theme = themes.find(params.theme);
+    let Some(user) = params.user else {
+        return AppError::MissingParameter("user")
+            .with_theme(theme)
+            .into_response();
+    };
+
+    let options = match options
+        .map_err(|e| e.body_text())
+        .and_then(|Query(options)| {
+            options.validate()?;
+            Ok(options)
+        }) {
+        Ok(options) => options,
+        Err(e) => {
+            return AppError::InvalidParameter(e)
+                .with_theme(theme)
+                .into_response()
+        }
+    };
+
+    if let Err(e) = check_user(&config, &user) {
+        return e.with_theme(theme).into_response();
+    }
+
+    let key = calendar_key(&user, options.year);
+    let ttl = config.cache.ttl_of(CacheKind::Calendar);
+    let (login, year) = (user.clone(), options.year);
+    let data = match cache::get_or_update(db.clone(), &key, ttl, move || {
+        let (github, login) = (github.clone(), login.clone());
+        async move { get_contribution_calendar(&github, &login, year).await }
+    })
+    .await
+    {
+        Ok(data) => data,
+        Err(e) => return e.with_theme(theme).into_response(),
+    };
+
+    let max_age = config
+        .cache
+        .max_age_of(CacheKind::Calendar, params.cache_seconds);
+    let response = CardResponse::new(&data, query.as_deref(), &theme, max_age);
+    if let Some(not_modified) = response.not_modified(&headers) {
+        return not_modified;
+    }
+    response
+        .card(
+            form_calendar_card(data, numbers, theme),
+            output,
+            db,
+            &user,
+            ttl,
+        )
+        .await
+}
+
+/// cache key of the calendar, one entry per year
+pub(super) fn calendar_key(user: &str, year: Option<i32>) -> String {
+    match year {
+        Some(year) => format!("{user}/{year}"),
+        None => user.to_string(),
+    }
+}
@@ -0,0 +1,251 @@
+//! json apis under `/api/v1`, the cached data behind the cards
+
+use std::{fmt::Debug, future::Future, time::SystemTime};
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use super::{
+    calendar::calendar_key, check_user, parse_query, repo::RepoParams, stats::stats_key, UserParams,
+};
+use crate::{
+    cache::{self, SharedCache},
+    cards::{use_languages, CalendarCardOptions, StatsCardOptions, TopLangsCardOptions},
+    config::{CacheKind, Config},
+    error::AppError,
+    github::{
+        calendar::get_contribution_calendar, get_user_github_stats, repo::get_repo_info,
+        stats::UserGithubStats, streak::get_user_streak, top_langs::get_top_langs, GithubClient,
+    },
+    utils::MonitorTime,
+};
+
+/// `{"data", "cached_at", "age"}`, the cached data and how old it is in seconds
+fn with_age<T: Serialize + MonitorTime>(data: &T) -> Map<String, Value> {
+    let create_at = *data.create_at();
+    let age = SystemTime::now()
+        .duration_since(create_at)
+        .unwrap_or_default()
+        .as_secs();
+    let mut body = Map::new();
+    body.insert("data".into(), json!(data));
+    body.insert(
+        "cached_at".into(),
+        json!(DateTime::<Utc>::from(create_at).to_rfc3339()),
+    );
+    body.insert("age".into(), json!(age));
+    body
+}
+
+/// check `user` and get its cached data of `kind`, `update` turns the user
+/// into the cache key and the fetch of a miss, errors are answered as json
+async fn get_user_data<T, F, Fut>(
+    config: &Config,
+    db: SharedCache,
+    user: Option<String>,
+    kind: CacheKind,
+    update: impl FnOnce(String) -> (String, F),
+) -> Result<T, Response>
+where
+    T: Clone + Debug + Decode + Encode + MonitorTime + Send + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T, AppError>> + Send + 'static,
+{
+    let Some(user) = user else {
+        return Err(AppError::MissingParameter("user").into_json());
+    };
+    check_user(config, &user).map_err(AppError::into_json)?;
+    let (key, func) = update(user);
+    cache::get_or_update(db, &key, config.cache.ttl_of(kind), func)
+        .await
+        .map_err(AppError::into_json)
+}
+
+/// the stats of the stats card, takes `exclude_repo` and `include_private`
+/// like the card, shared with the rank api
+pub(super) async fn get_stats_data(
+    config: &Config,
+    db: SharedCache,
+    github: GithubClient,
+    user: Option<String>,
+    options: &StatsCardOptions,
+) -> Result<UserGithubStats, Response> {
+    let data = get_user_data(config, db, user, CacheKind::Stats, |user| {
+        let (key, exclude_repo) = stats_key(&user, &options.exclude_repo);
+        (key, move || {
+            let (github, login, exclude_repo) =
+                (github.clone(), user.clone(), exclude_repo.clone());
+            async move { get_user_github_stats(&github, &login, &exclude_repo).await }
+        })
+    })
+    .await?;
+    Ok(if options.include_private {
+        data.with_private()
+    } else {
+        data
+    })
+}
+
+/// `GET /api/v1/stats?user=`, the stats card data with its rank,
+/// takes `exclude_repo`, `include_private` and `rank_model` like the card
+pub async fn get_stats_json(
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<StatsCardOptions>, QueryRejection>,
+    State(config): State<Config>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        Err(e) => return e.into_json(),
+    };
+    let options = match parse_query(options) {
+        Ok(options) => options,
+        Err(e) => return e.into_json(),
+    };
+    let data = match get_stats_data(&config, db, github, params.user, &options).await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+    let rank = data.calculate_rank(
+        &config.rank,
+        options.rank_model.unwrap_or(config.rank.model),
+    );
+    let mut body = with_age(&data);
+    body.insert("rank".into(), json!(rank));
+    Json(body).into_response()
+}
+
+/// `GET /api/v1/top-langs?user=`, languages of each repo and the languages
+/// the card shows, takes the options of the card
+pub async fn get_top_langs_json(
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<TopLangsCardOptions>, QueryRejection>,
+    State(config): State<Config>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        Err(e) => return e.into_json(),
+    };
+    let options = match options
+        .map_err(|e| e.body_text())
+        .and_then(|Query(options)| {
+            options.validate()?;
+            Ok(options)
+        }) {
+        Ok(options) => options,
+        Err(e) => return AppError::InvalidParameter(e).into_json(),
+    };
+
+    let data = get_user_data(&config, db, params.user, CacheKind::TopLangs, |user| {
+        (user.clone(), move || {
+            let (github, login) = (github.clone(), user.clone());
+            async move { get_top_langs(&github, &login).await }
+        })
+    })
+    .await;
+    match data {
+        Ok(data) => {
+            let mut body = with_age(&data);
+            body.insert("langs".into(), json!(use_languages(&data, &options)));
+            Json(body).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+/// `GET /api/v1/streak?user=`
+pub async fn get_streak_json(
+    params: Result<Query<UserParams>, QueryRejection>,
+    State(config): State<Config>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        Err(e) => return e.into_json(),
+    };
+    let data = get_user_data(&config, db, params.user, CacheKind::Streak, |user| {
+        (user.clone(), move || {
+            let (github, login) = (github.clone(), user.clone());
+            async move { get_user_streak(&github, &login).await }
+        })
+    })
+    .await;
+    match data {
+        Ok(data) => Json(with_age(&data)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// `GET /api/v1/calendar?user=&year=`
+pub async fn get_calendar_json(
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<CalendarCardOptions>, QueryRejection>,
+    State(config): State<Config>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        Err(e) => return e.into_json(),
+    };
+    let options = match options
+        .map_err(|e| e.body_text())
+        .and_then(|Query(options)| {
+            options.validate()?;
+            Ok(options)
+        }) {
+        Ok(options) => options,
+        Err(e) => return AppError::InvalidParameter(e).into_json(),
+    };
+
+    let year = options.year;
+    let data = get_user_data(&config, db, params.user, CacheKind::Calendar, |user| {
+        (calendar_key(&user, year), move || {
+            let (github, login) = (github.clone(), user.clone());
+            async move { get_contribution_calendar(&github, &login, year).await }
+        })
+    })
+    .await;
+    match data {
+        Ok(data) => Json(with_age(&data)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// `GET /api/v1/pin?user=&repo=`
+pub async fn get_repo_json(
+    params: Result<Query<UserParams>, QueryRejection>,
+    Query(RepoParams { repo }): Query<RepoParams>,
+    State(config): State<Config>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        Err(e) => return e.into_json(),
+    };
+    let Some(repo) = repo else {
+        return AppError::MissingParameter("repo").into_json();
+    };
+    let data = get_user_data(&config, db, params.user, CacheKind::Pin, |user| {
+        (format!("{user}/{repo}"), move || {
+            let (github, login, repo) = (github.clone(), user.clone(), repo.clone());
+            async move { get_repo_info(&github, &login, &repo).await }
+        })
+    })
+    .await;
+    match data {
+        Ok(data) => Json(with_age(&data)).into_response(),
+        Err(response) => response,
+    }
+}
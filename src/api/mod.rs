@@ -1,93 +1,396 @@
 //! contains all api services
 
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
 
 use askama::Template;
 use axum::{
-    extract::FromRef,
-    http::StatusCode,
+    extract::{rejection::QueryRejection, FromRef, Query},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Response},
-    routing::get,
+    routing::{delete, get, post},
     Router,
 };
-use tokio::net::TcpListener;
-use tracing::info;
+use bincode::Encode;
+use serde::Deserialize;
+use svg::Document;
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, SignalKind},
+    sync::Semaphore,
+};
+use tracing::{error, info, warn};
 
+mod auth;
 mod cache;
+mod calendar;
 mod ip;
+mod json;
+mod rank;
+mod repo;
 mod stats;
 mod status;
+mod streak;
 mod themes;
 mod top_langs;
 
 use crate::{
-    cache::SharedCache,
-    config::{Config, ListenStack, Themes},
+    cache::{get_or_update, load_snapshot, save_snapshot, spawn_cleanup, CacheStore, SharedCache},
+    cards::{png_scale, png_size, rasterize, NumberFormat, NumberFormatter},
+    config::{Config, ListenStack, Theme, Themes},
+    error::AppError,
+    github::{validate_login, GithubClient},
+    utils::{deserialize_lenient_u64, http_date, parse_http_date, MonitorTime},
 };
 
+/// query parameters shared by all card apis
+#[derive(Debug, Deserialize)]
+struct UserParams {
+    user: Option<String>,
+    theme: Option<String>,
+    /// `Cache-Control` max age, clamped to `cache { cache_seconds }`
+    #[serde(default, deserialize_with = "deserialize_lenient_u64")]
+    cache_seconds: Option<u64>,
+    #[serde(default)]
+    format: CardFormat,
+    /// pixel ratio of png cards, 0.5 to 4
+    scale: Option<f32>,
+    #[serde(default)]
+    number_format: NumberFormat,
+    /// digit grouping and decimal separator, like `de` or `fr-CA`
+    locale: Option<String>,
+}
+
+impl UserParams {
+    /// `format` and `scale` of the card
+    fn output(&self) -> (CardFormat, Option<f32>) {
+        (self.format, self.scale)
+    }
+
+    /// formatter of the numbers on the card
+    fn numbers(&self) -> NumberFormatter {
+        NumberFormatter::new(self.number_format, self.locale.as_deref())
+    }
+}
+
+/// output of the card apis, `format=png` for places that do not render svg
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CardFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+/// rasterisations running at once, each holds a pixmap of up to 16MB
+static RASTERIZE_PERMITS: Semaphore = Semaphore::const_new(4);
+
+/// the parsed query string, `InvalidParameter` if it can not be parsed
+fn parse_query<T>(query: Result<Query<T>, QueryRejection>) -> Result<T, AppError> {
+    query
+        .map(|Query(query)| query)
+        .map_err(|e| AppError::InvalidParameter(e.body_text()))
+}
+
+/// check the username is valid and allowed by `allow_users`
+fn check_user(config: &Config, user: &str) -> Result<(), AppError> {
+    validate_login(user)?;
+    if !config.allow_users.is_empty() && !config.allow_users.iter().any(|i| i == user) {
+        return Err(AppError::NotAllowed);
+    }
+    Ok(())
+}
+
+/// caching headers of a card, answers conditional requests before rendering it
+struct CardResponse {
+    etag: String,
+    last_modified: SystemTime,
+    max_age: Duration,
+    /// theme of the error card when the card can not be rasterised
+    theme: Theme,
+}
+
+impl CardResponse {
+    /// the etag covers the cached data, the query string with theme and options,
+    /// and the theme itself
+    fn new<T>(data: &T, query: Option<&str>, theme: &Theme, max_age: Duration) -> Self
+    where
+        T: Encode + MonitorTime,
+    {
+        let mut hasher = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        bincode::encode_to_vec(data, bincode::config::standard())
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        query.hash(&mut hasher);
+        format!("{theme:?}").hash(&mut hasher);
+        Self {
+            etag: format!("\"{:016x}\"", hasher.finish()),
+            last_modified: *data.create_at(),
+            max_age,
+            theme: theme.clone(),
+        }
+    }
+
+    fn headers(&self) -> [(HeaderName, String); 4] {
+        [
+            (header::ETAG, self.etag.clone()),
+            (header::LAST_MODIFIED, http_date(self.last_modified)),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", self.max_age.as_secs()),
+            ),
+            (header::EXPIRES, http_date(SystemTime::now() + self.max_age)),
+        ]
+    }
+
+    /// `If-None-Match` takes precedence over `If-Modified-Since`
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|i| i.to_str().ok())
+        {
+            return if_none_match
+                .split(',')
+                .map(|i| i.trim().trim_start_matches("W/"))
+                .any(|i| i == "*" || i == self.etag);
+        }
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|i| i.to_str().ok())
+            .and_then(parse_http_date)
+            .is_some_and(|since| {
+                // http dates have no sub-second precision
+                self.last_modified
+                    .duration_since(since)
+                    .map_or(true, |i| i.as_secs() == 0)
+            })
+    }
+
+    /// `304 Not Modified` if the client already has this card
+    fn not_modified(&self, headers: &HeaderMap) -> Option<Response> {
+        self.is_fresh(headers)
+            .then(|| (StatusCode::NOT_MODIFIED, self.headers()).into_response())
+    }
+
+    /// the card in the requested format
+    async fn card(
+        self,
+        document: Document,
+        (format, scale): (CardFormat, Option<f32>),
+        db: SharedCache,
+        user: &str,
+        ttl: Duration,
+    ) -> Response {
+        match format {
+            CardFormat::Svg => self.svg(document),
+            CardFormat::Png => self.png(document, png_scale(scale), db, user, ttl).await,
+        }
+    }
+
+    /// rasterised bytes are cached by the rendered svg and the scale, so query
+    /// parameters the card ignores do not rasterise it again
+    async fn png(
+        self,
+        document: Document,
+        scale: f32,
+        db: SharedCache,
+        user: &str,
+        ttl: Duration,
+    ) -> Response {
+        let size = |name| {
+            document
+                .get_attributes()
+                .get(name)
+                .and_then(|i| i.parse::<f32>().ok())
+                .unwrap_or_default()
+        };
+        if let Err(e) = png_size(size("width"), size("height"), scale) {
+            return AppError::InvalidParameter(e)
+                .with_theme(self.theme)
+                .into_response();
+        }
+
+        let svg = document.to_string();
+        let mut hasher = DefaultHasher::new();
+        svg.hash(&mut hasher);
+        scale.to_bits().hash(&mut hasher);
+        let key = format!("{user}/png={:016x}", hasher.finish());
+        let png = get_or_update(db, &key, ttl, move || {
+            let svg = svg.clone();
+            async move {
+                let _permit = RASTERIZE_PERMITS
+                    .acquire()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                tokio::task::spawn_blocking(move || rasterize(&svg, scale))
+                    .await
+                    .map_err(|e| e.to_string())?
+            }
+        })
+        .await;
+        match png {
+            Ok(png) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "image/png")],
+                self.headers(),
+                png.bytes,
+            )
+                .into_response(),
+            Err(e) => {
+                error!("failed to rasterise card: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+
+    fn svg(self, document: Document) -> Response {
+        (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
+            self.headers(),
+            document.to_string(),
+        )
+            .into_response()
+    }
+}
+
 #[derive(Debug, Clone, FromRef)]
 struct AppState {
     config: Config,
     themes: Themes,
     cache: SharedCache,
+    github: GithubClient,
 }
 
-pub async fn run(config: Config, themes: Themes) {
-    let localhost_v4 = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), config.listen_port);
-    let localhost_v6 = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), config.listen_port);
-
-    let listen_stack = &config.listen_stack.clone();
+/// all routes with a memory only cache, to embed the service or test it
+pub fn app(config: Config, themes: Themes) -> Router {
+    let cache = Arc::new(RwLock::new(CacheStore::new(&config.cache)));
+    router(config, themes, cache)
+}
 
+fn router(config: Config, themes: Themes, cache: SharedCache) -> Router {
+    let github = GithubClient::new(&config.github_api_tokens, &config.github)
+        .expect("failed to build github client");
     let app_state = AppState {
         config,
         themes,
-        cache: SharedCache::default(),
+        cache,
+        github,
     };
+    let admin = Router::new()
+        .route(
+            "/cache/entries",
+            get(cache::list_entries_api).delete(cache::delete_entry_api),
+        )
+        .route("/cache/refresh", post(cache::refresh_entry_api))
+        .route("/cache/users/:user", delete(cache::purge_user_api))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth::require_admin,
+        ));
     // build our application with a route
-    let app = Router::new()
+    Router::new()
         .route("/api/v1/status", get(status::get_status_json))
+        .route("/api/v1/rank", get(rank::get_rank_json))
+        .route("/api/v1/stats", get(json::get_stats_json))
+        .route("/api/v1/top-langs", get(json::get_top_langs_json))
+        .route("/api/v1/pin", get(json::get_repo_json))
+        .route("/api/v1/streak", get(json::get_streak_json))
+        .route("/api/v1/calendar", get(json::get_calendar_json))
         .route("/status", get(status::get_status))
         .route("/ip", get(ip::get_ip))
         .route("/themes", get(themes::list_themes_api))
         .route("/stats", get(stats::get_user_stats_svg))
         .route("/stats/top-langs", get(top_langs::get_top_langs_svg))
+        .route("/stats/pin", get(repo::get_repo_pin_svg))
+        .route("/stats/streak", get(streak::get_streak_svg))
+        .route("/stats/calendar", get(calendar::get_calendar_svg))
         .route("/cache/keys", get(cache::list_keys_api))
+        .route("/cache/stats", get(cache::get_stats_api))
+        .merge(admin)
         // add a fallback service for handling routes to unknown paths
         .fallback(handler_404)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth::guard_routes,
+        ))
         .with_state(app_state)
-        .layer(tower_http::limit::RequestBodyLimitLayer::new(1024));
+        .layer(tower_http::limit::RequestBodyLimitLayer::new(1024))
+}
+
+pub async fn run(config: Config, themes: Themes) {
+    let localhost_v4 = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), config.listen_port);
+    let localhost_v6 = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), config.listen_port);
+
+    let listen_stack = &config.listen_stack.clone();
+
+    let cache = Arc::new(RwLock::new(CacheStore::new(&config.cache)));
+    let snapshot_path = config.cache.path.clone();
+    if let Some(path) = &snapshot_path {
+        match load_snapshot(&cache, path).await {
+            Ok(count) => info!("{} cache entries loaded from {}", count, path.display()),
+            Err(e) => warn!("discard cache snapshot {}: {}", path.display(), e),
+        }
+    }
+    spawn_cleanup(
+        cache.clone(),
+        config.cache.cleanup_interval,
+        snapshot_path.clone(),
+    );
+
+    if !config.admin.has_credentials() {
+        warn!("no admin credentials, guarded routes and the admin api are disabled");
+    }
+    let app = router(config, themes, cache.clone());
 
     // run it
-    if let ListenStack::Both = listen_stack {
-        info!("listening v4 on http://{}", &localhost_v4);
-        info!("listening v6 on http://{}", &localhost_v6);
-        let incoming_v6 = TcpListener::bind(localhost_v6).await.unwrap();
-        axum::serve(
-            incoming_v6,
-            app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await
-        .unwrap();
-    } else {
-        let incoming = match listen_stack {
-            ListenStack::V4 => {
-                info!("listening v4 on http://{}", &localhost_v4);
-                TcpListener::bind(localhost_v4).await.unwrap()
-            }
-            ListenStack::V6 => {
-                info!("listening v6 on http://{}", &localhost_v6);
-                TcpListener::bind(localhost_v6).await.unwrap()
-            }
-            _ => unreachable!(),
-        };
+    let incoming = match listen_stack {
+        ListenStack::Both => {
+            info!("listening v4 on http://{}", &localhost_v4);
+            info!("listening v6 on http://{}", &localhost_v6);
+            TcpListener::bind(localhost_v6).await.unwrap()
+        }
+        ListenStack::V4 => {
+            info!("listening v4 on http://{}", &localhost_v4);
+            TcpListener::bind(localhost_v4).await.unwrap()
+        }
+        ListenStack::V6 => {
+            info!("listening v6 on http://{}", &localhost_v6);
+            TcpListener::bind(localhost_v6).await.unwrap()
+        }
+    };
+    // in-flight requests are finished before the snapshot is saved
+    axum::serve(
+        incoming,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
 
-        axum::serve(
-            incoming,
-            app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await
-        .unwrap();
+    if let Some(path) = &snapshot_path {
+        if let Err(e) = save_snapshot(&cache, path).await {
+            warn!("failed to save cache snapshot {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// wait for ctrl-c or `SIGTERM` from systemd
+async fn shutdown_signal() {
+    let terminate = async {
+        signal(SignalKind::terminate()).unwrap().recv().await;
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate => {},
     }
+    info!("shutting down");
 }
 
 struct HtmlTemplate<T>(T);
@@ -111,3 +414,37 @@ where
 async fn handler_404() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "nothing to see here")
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_card_response_is_fresh() {
+        let response = CardResponse {
+            etag: "\"abc\"".to_string(),
+            last_modified: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500),
+            max_age: Duration::from_secs(60),
+            theme: Theme::default(),
+        };
+        let check = |name: HeaderName, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+            response.is_fresh(&headers)
+        };
+
+        assert!(!response.is_fresh(&HeaderMap::new()));
+        assert!(check(header::IF_NONE_MATCH, "\"abc\""));
+        assert!(check(header::IF_NONE_MATCH, "\"xyz\", W/\"abc\""));
+        assert!(!check(header::IF_NONE_MATCH, "\"xyz\""));
+
+        let last_modified = http_date(response.last_modified);
+        assert!(check(header::IF_MODIFIED_SINCE, &last_modified));
+        assert!(!check(
+            header::IF_MODIFIED_SINCE,
+            "Tue, 14 Nov 2023 22:13:19 GMT"
+        ));
+    }
+}
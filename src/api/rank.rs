@@ -0,0 +1,46 @@
+//! rank api, the rank of the stats card as json
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+
+use super::{json::get_stats_data, parse_query, UserParams};
+use crate::{cache::SharedCache, cards::StatsCardOptions, config::Config, github::GithubClient};
+
+/// `GET /api/v1/rank?user=`, shares the cached stats of the stats card,
+/// takes `exclude_repo`, `include_private` and `rank_model` like the card
+pub async fn get_rank_json(
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<StatsCardOptions>, QueryRejection>,
+    State(config): State<Config>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        Err(e) => return e.into_json(),
+    };
+    let options = match parse_query(options) {
+        Ok(options) => options,
+        Err(e) => return e.into_json(),
+    };
+    let data = match get_stats_data(&config, db, github, params.user, &options).await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+    let rank = data.calculate_rank(
+        &config.rank,
+        options.rank_model.unwrap_or(config.rank.model),
+    );
+    Json(json!({
+        "login": data.login,
+        "name": data.name,
+        "model": rank.model,
+        "level": rank.level,
+        "score": rank.score,
+        "percentile": rank.percentile,
+    }))
+    .into_response()
+}
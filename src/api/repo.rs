@@ -0,0 +1,78 @@
+//! github repository pin api
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, RawQuery, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use super::{check_user, parse_query, CardResponse, UserParams};
+use crate::{
+    cache::{self, SharedCache},
+    cards::form_repo_card,
+    config::{CacheKind, Config, Themes},
+    error::AppError,
+    github::{repo::get_repo_info, GithubClient},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct RepoParams {
+    pub(super) repo: Option<String>,
+}
+
+/// get a single repository from github, and return a svg
+/// cache enabled
+#[allow(clippy::too_many_arguments)]
+pub async fn get_repo_pin_svg(
+    params: Result<Query<UserParams>, QueryRejection>,
+    Query(RepoParams { repo }): Query<RepoParams>,
+    State(config): State<Config>,
+    State(themes): State<Themes>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        // the theme is part of the query that failed
+        Err(e) => return e.with_theme(themes.find(None::<&str>)).into_response(),
+    };
+    let output = params.output();
+    let numbers = params.numbers();
+    let theme = themes.find(params.theme);
+    let (Some(user), Some(repo)) = (params.user, repo) else {
+        return AppError::MissingParameter("user or repo")
+            .with_theme(theme)
+            .into_response();
+    };
+
+    if let Err(e) = check_user(&config, &user) {
+        return e.with_theme(theme).into_response();
+    }
+
+    let key = format!("{user}/{repo}");
+    let ttl = config.cache.ttl_of(CacheKind::Pin);
+    let login = user.clone();
+    let data = match cache::get_or_update(db.clone(), &key, ttl, move || {
+        let (github, login, repo) = (github.clone(), login.clone(), repo.clone());
+        async move { get_repo_info(&github, &login, &repo).await }
+    })
+    .await
+    {
+        Ok(data) => data,
+        Err(e) => return e.with_theme(theme).into_response(),
+    };
+
+    let max_age = config
+        .cache
+        .max_age_of(CacheKind::Pin, params.cache_seconds);
+    let response = CardResponse::new(&data, query.as_deref(), &theme, max_age);
+    if let Some(not_modified) = response.not_modified(&headers) {
+        return not_modified;
+    }
+    response
+        .card(form_repo_card(data, numbers, theme), output, db, &user, ttl)
+        .await
+}
@@ -1,48 +1,114 @@
 //! github stats api
 
-use std::collections::HashMap;
-
 use axum::{
-    extract::{Query, State},
-    http::{header, StatusCode},
+    extract::{rejection::QueryRejection, Query, RawQuery, State},
+    http::HeaderMap,
     response::{IntoResponse, Response},
 };
 
+use super::{check_user, parse_query, CardResponse, UserParams};
 use crate::{
     cache::{self, SharedCache},
-    cards::form_stats_card,
-    config::{Config, Themes},
-    github::get_user_github_stats,
+    cards::{form_stats_card, StatsCardOptions},
+    config::{CacheKind, Config, Themes},
+    error::AppError,
+    github::{get_user_github_stats, GithubClient},
 };
 
 /// get user stats from github, and return a svg
 /// cache enabled
+#[allow(clippy::too_many_arguments)]
 pub async fn get_user_stats_svg(
-    Query(params): Query<HashMap<String, String>>,
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<StatsCardOptions>, QueryRejection>,
     State(config): State<Config>,
     State(themes): State<Themes>,
     State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
 ) -> Response {
-    if params.get("user").is_none() {
-        return (StatusCode::NOT_FOUND, "no user").into_response();
-    }
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        // the theme is part of the query that failed
+        Err(e) => return e.with_theme(themes.find(None::<&str>)).into_response(),
+    };
+    let output = params.output();
+    let numbers = params.numbers();
+    let theme = themes.find(params.theme);
+    let Some(user) = params.user else {
+        return AppError::MissingParameter("user")
+            .with_theme(theme)
+            .into_response();
+    };
 
-    let user = params.get("user").unwrap().to_owned();
+    let options = match options
+        .map_err(|e| e.body_text())
+        .and_then(|Query(options)| {
+            options.validate()?;
+            Ok(options)
+        }) {
+        Ok(options) => options,
+        Err(e) => {
+            return AppError::InvalidParameter(e)
+                .with_theme(theme)
+                .into_response()
+        }
+    };
 
-    if !config.allow_users.is_empty() && !config.allow_users.contains(&user) {
-        return (StatusCode::FORBIDDEN, "user not in allow list").into_response();
+    if let Err(e) = check_user(&config, &user) {
+        return e.with_theme(theme).into_response();
     }
 
-    let data = cache::get_or_update(db, &user, || {
-        get_user_github_stats(&config.github_api_token, &user)
+    let (key, exclude_repo) = stats_key(&user, &options.exclude_repo);
+    let ttl = config.cache.ttl_of(CacheKind::Stats);
+    let login = user.clone();
+    let data = match cache::get_or_update(db.clone(), &key, ttl, move || {
+        let (github, login, exclude_repo) = (github.clone(), login.clone(), exclude_repo.clone());
+        async move { get_user_github_stats(&github, &login, &exclude_repo).await }
     })
-    .await;
-
-    let theme = themes.find(params.get("theme"));
-    (
-        StatusCode::OK,
-        [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
-        form_stats_card(data, false, true, theme).to_string(),
-    )
-        .into_response()
+    .await
+    {
+        Ok(data) => data,
+        Err(e) => return e.with_theme(theme).into_response(),
+    };
+
+    let max_age = config
+        .cache
+        .max_age_of(CacheKind::Stats, params.cache_seconds);
+    let response = CardResponse::new(&data, query.as_deref(), &theme, max_age);
+    if let Some(not_modified) = response.not_modified(&headers) {
+        return not_modified;
+    }
+    let data = if options.include_private {
+        data.with_private()
+    } else {
+        data
+    };
+    let rank = data.calculate_rank(
+        &config.rank,
+        options.rank_model.unwrap_or(config.rank.model),
+    );
+    response
+        .card(
+            form_stats_card(data, rank, &options, numbers, theme),
+            output,
+            db,
+            &user,
+            ttl,
+        )
+        .await
+}
+
+/// cache key of the stats, stars differ by `exclude_repo`
+pub(super) fn stats_key(user: &str, exclude_repo: &[String]) -> (String, Vec<String>) {
+    let mut exclude_repo = exclude_repo.to_vec();
+    exclude_repo.sort();
+    exclude_repo.dedup();
+    let key = if exclude_repo.is_empty() {
+        user.to_string()
+    } else {
+        format!("{user}/exclude_repo={}", exclude_repo.join(","))
+    };
+    (key, exclude_repo)
 }
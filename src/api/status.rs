@@ -7,16 +7,22 @@ use axum::{
 use serde_json::json;
 
 use super::HtmlTemplate;
-use crate::{config::Config, status::Status};
+use crate::{config::Config, github::GithubClient, status::Status};
 
 /// show server status: use systemd status service
-pub async fn get_status(State(config): State<Config>) -> impl IntoResponse {
-    let status = Status::init(config.services).await;
+pub async fn get_status(
+    State(config): State<Config>,
+    State(github): State<GithubClient>,
+) -> impl IntoResponse {
+    let status = Status::init(config.services, github.budget()).await;
     HtmlTemplate(status)
 }
 
 /// show server status: use systemd status service
-pub async fn get_status_json(State(config): State<Config>) -> impl IntoResponse {
-    let status = Status::init(config.services).await;
+pub async fn get_status_json(
+    State(config): State<Config>,
+    State(github): State<GithubClient>,
+) -> impl IntoResponse {
+    let status = Status::init(config.services, github.budget()).await;
     Json(json!(status))
 }
@@ -0,0 +1,75 @@
+//! github contribution streak api
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, RawQuery, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+
+use super::{check_user, parse_query, CardResponse, UserParams};
+use crate::{
+    cache::{self, SharedCache},
+    cards::form_streak_card,
+    config::{CacheKind, Config, Themes},
+    error::AppError,
+    github::{streak::get_user_streak, GithubClient},
+};
+
+/// get user contribution streak from github, and return a svg
+/// cache enabled
+pub async fn get_streak_svg(
+    params: Result<Query<UserParams>, QueryRejection>,
+    State(config): State<Config>,
+    State(themes): State<Themes>,
+    State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        // the theme is part of the query that failed
+        Err(e) => return e.with_theme(themes.find(None::<&str>)).into_response(),
+    };
+    let output = params.output();
+    let numbers = params.numbers();
+    let theme = themes.find(params.theme);
+    let Some(user) = params.user else {
+        return AppError::MissingParameter("user")
+            .with_theme(theme)
+            .into_response();
+    };
+
+    if let Err(e) = check_user(&config, &user) {
+        return e.with_theme(theme).into_response();
+    }
+
+    let ttl = config.cache.ttl_of(CacheKind::Streak);
+    let login = user.clone();
+    let data = match cache::get_or_update(db.clone(), &user, ttl, move || {
+        let (github, login) = (github.clone(), login.clone());
+        async move { get_user_streak(&github, &login).await }
+    })
+    .await
+    {
+        Ok(data) => data,
+        Err(e) => return e.with_theme(theme).into_response(),
+    };
+
+    let max_age = config
+        .cache
+        .max_age_of(CacheKind::Streak, params.cache_seconds);
+    let response = CardResponse::new(&data, query.as_deref(), &theme, max_age);
+    if let Some(not_modified) = response.not_modified(&headers) {
+        return not_modified;
+    }
+    response
+        .card(
+            form_streak_card(data, numbers, theme),
+            output,
+            db,
+            &user,
+            ttl,
+        )
+        .await
+}
@@ -1,50 +1,90 @@
-use std::collections::HashMap;
+//! github top languages api
 
 use axum::{
-    extract::{Query, State},
-    http::{header, StatusCode},
+    extract::{rejection::QueryRejection, Query, RawQuery, State},
+    http::HeaderMap,
     response::IntoResponse,
 };
 
+use super::{check_user, parse_query, CardResponse, UserParams};
 use crate::{
     cache::{self, SharedCache},
-    cards::form_top_langs_card,
-    config::{Config, Themes},
-    github::top_langs::get_top_langs,
+    cards::{form_top_langs_card, TopLangsCardOptions},
+    config::{CacheKind, Config, Themes},
+    error::AppError,
+    github::{top_langs::get_top_langs, GithubClient},
 };
 
 /// get user used top programming languages from github, and return a svg
 /// cache enabled
+#[allow(clippy::too_many_arguments)]
 pub async fn get_top_langs_svg(
-    Query(params): Query<HashMap<String, String>>,
+    params: Result<Query<UserParams>, QueryRejection>,
+    options: Result<Query<TopLangsCardOptions>, QueryRejection>,
     State(config): State<Config>,
     State(themes): State<Themes>,
     State(db): State<SharedCache>,
+    State(github): State<GithubClient>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    if params.get("user").is_none() {
-        return (StatusCode::NOT_FOUND, "no user").into_response();
-    }
+    let params = match parse_query(params) {
+        Ok(params) => params,
+        // the theme is part of the query that failed
+        Err(e) => return e.with_theme(themes.find(None::<&str>)).into_response(),
+    };
+    let output = params.output();
+    let numbers = params.numbers();
+    let theme = themes.find(params.theme);
+    let Some(user) = params.user else {
+        return AppError::MissingParameter("user")
+            .with_theme(theme)
+            .into_response();
+    };
 
-    let user = params.get("user").unwrap().to_owned();
-    let hide: Vec<String> = {
-        if let Some(hide) = params.get("hide") {
-            hide.split(',').map(|i| i.to_string()).collect()
-        } else {
-            vec![]
+    let options = match options
+        .map_err(|e| e.body_text())
+        .and_then(|Query(options)| {
+            options.validate()?;
+            Ok(options)
+        }) {
+        Ok(options) => options,
+        Err(e) => {
+            return AppError::InvalidParameter(e)
+                .with_theme(theme)
+                .into_response()
         }
     };
 
-    if !config.allow_users.is_empty() && !config.allow_users.contains(&user) {
-        return (StatusCode::FORBIDDEN, "user not in allow list").into_response();
+    if let Err(e) = check_user(&config, &user) {
+        return e.with_theme(theme).into_response();
     }
 
-    let data =
-        cache::get_or_update(db, &user, || get_top_langs(&config.github_api_token, &user)).await;
-    let theme = themes.find(params.get("theme"));
-    (
-        StatusCode::OK,
-        [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
-        form_top_langs_card(data, hide, None, None, theme).to_string(),
-    )
-        .into_response()
+    let ttl = config.cache.ttl_of(CacheKind::TopLangs);
+    let login = user.clone();
+    let data = match cache::get_or_update(db.clone(), &user, ttl, move || {
+        let (github, login) = (github.clone(), login.clone());
+        async move { get_top_langs(&github, &login).await }
+    })
+    .await
+    {
+        Ok(data) => data,
+        Err(e) => return e.with_theme(theme).into_response(),
+    };
+    let max_age = config
+        .cache
+        .max_age_of(CacheKind::TopLangs, params.cache_seconds);
+    let response = CardResponse::new(&data, query.as_deref(), &theme, max_age);
+    if let Some(not_modified) = response.not_modified(&headers) {
+        return not_modified;
+    }
+    response
+        .card(
+            form_top_langs_card(data, &options, numbers, theme),
+            output,
+            db,
+            &user,
+            ttl,
+        )
+        .await
 }
@@ -2,48 +2,413 @@
 //! use `bincode` to encode/decode
 
 use std::{
-    any::type_name,
+    any::{type_name, Any},
     collections::HashMap,
     fmt::Debug,
     future::Future,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    pin::Pin,
     sync::{Arc, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use bincode::{Decode, Encode};
-use tracing::{info, trace};
+use color_eyre::{eyre::eyre, Result};
+use tokio::{
+    fs,
+    sync::{Mutex, OwnedMutexGuard},
+    task::JoinHandle,
+    time,
+};
+use tracing::{debug, info, trace, warn};
 
-use crate::utils::MonitorTime;
+use crate::{config::CacheConfig, utils::MonitorTime};
 
-pub const TIMEOUT_SECS: Duration = Duration::from_secs(60 * 60);
+/// snapshots written by another version may not decode, discard them
+const SNAPSHOT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// layout of the cached types, bump it whenever one of them changes, as bincode
+/// decodes other layouts into the wrong fields instead of failing
+const SCHEMA_VERSION: u32 = 2;
 
-/// TODO: need clean up expired cache
+/// shared by all handlers, see `CacheStore`
 pub type SharedCache = Arc<RwLock<CacheStore>>;
 
-#[derive(Default, Debug)]
+/// error of the last failed fetch of a key, for the requests that waited on it
+type InflightError = Option<Arc<dyn Any + Send + Sync>>;
+
+#[derive(Debug)]
+struct CacheEntry {
+    data: Vec<u8>,
+    ttl: Duration,
+    inserted_at: Instant,
+    last_access: Instant,
+}
+
+impl CacheEntry {
+    fn new(data: Vec<u8>, ttl: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            data,
+            ttl,
+            inserted_at: now,
+            last_access: now,
+        }
+    }
+}
+
+/// on-disk format of `CacheStore`
+#[derive(Decode, Encode)]
+struct Snapshot {
+    version: String,
+    schema: u32,
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Decode, Encode)]
+struct SnapshotEntry {
+    key: String,
+    data: Vec<u8>,
+    /// seconds since inserted
+    age: u64,
+    ttl: u64,
+}
+
+/// fetches the data of a key again and caches it, registered by `get_or_update`
+#[derive(Clone)]
+struct Refresher(Arc<dyn Fn(SharedCache) -> RefreshFuture + Send + Sync>);
+
+type RefreshFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+impl Debug for Refresher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Refresher")
+    }
+}
+
+/// what `/cache/entries` shows of an entry
+#[derive(Debug, Clone)]
+pub struct EntryInfo {
+    pub key: String,
+    /// decoded type, the prefix of the key
+    pub data_type: String,
+    pub age: Duration,
+    pub ttl: Duration,
+    /// encoded size in bytes
+    pub size: usize,
+    /// requested since start, so it can be refreshed
+    pub refreshable: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// bincode encoded data, evicted `stale_ttl` after its own ttl and limited to `max_entries`
+#[derive(Debug)]
 pub struct CacheStore {
-    db: HashMap<String, Vec<u8>>,
+    db: HashMap<String, CacheEntry>,
+    stale_ttl: Duration,
+    max_entries: usize,
+    stats: CacheStats,
+    /// held while fetching a key, so concurrent requests share one fetch
+    inflight: HashMap<String, Arc<Mutex<InflightError>>>,
+    refreshers: HashMap<String, Refresher>,
+}
+
+impl Default for CacheStore {
+    fn default() -> Self {
+        Self::new(&CacheConfig::default())
+    }
+}
+
+impl CacheStore {
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            db: HashMap::new(),
+            stale_ttl: config.stale_ttl,
+            max_entries: config.max_entries,
+            stats: CacheStats::default(),
+            inflight: HashMap::new(),
+            refreshers: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&[u8]> {
+        match self.db.get_mut(key) {
+            Some(entry) => {
+                self.stats.hits += 1;
+                entry.last_access = Instant::now();
+                Some(&entry.data)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: String, data: Vec<u8>, ttl: Duration) {
+        self.insert_entry(key, CacheEntry::new(data, ttl));
+    }
+
+    /// insert or replace `key`, evict least recently used entries when full
+    fn insert_entry(&mut self, key: String, entry: CacheEntry) {
+        while !self.db.contains_key(&key) && self.db.len() >= self.max_entries {
+            let Some(lru_key) = self
+                .db
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&lru_key);
+            self.stats.evictions += 1;
+            debug!("[Cache][EVICT] {}", lru_key);
+        }
+        self.db.insert(key, entry);
+    }
+
+    /// remove entries older than `max_age`, return the count of removed entries
+    pub fn evict_expired(&mut self) -> usize {
+        let stale_ttl = self.stale_ttl;
+        let before = self.db.len();
+        self.db
+            .retain(|_, entry| entry.inserted_at.elapsed() <= entry.ttl + stale_ttl);
+        let evicted = before - self.db.len();
+        self.stats.evictions += evicted as u64;
+        let db = &self.db;
+        self.refreshers.retain(|key, _| db.contains_key(key));
+        evicted
+    }
+
+    /// remove `key`, return whether it was cached
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.refreshers.remove(key);
+        self.db.remove(key).is_some()
+    }
+
+    /// remove all data of `user` (`user` and `user/...` keys) of all types,
+    /// return the count of removed entries
+    pub fn purge_user(&mut self, user: &str) -> usize {
+        let prefix = format!("{}/", user.to_lowercase());
+        let keys = self
+            .db
+            .keys()
+            .filter(|full_key| {
+                let key = split_key(full_key).1.to_lowercase();
+                key == user.to_lowercase() || key.starts_with(&prefix)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    /// all entries sorted by key
+    pub fn entries(&self) -> Vec<EntryInfo> {
+        let mut entries = self
+            .db
+            .iter()
+            .map(|(key, entry)| EntryInfo {
+                key: key.clone(),
+                data_type: split_key(key).0.to_string(),
+                age: entry.inserted_at.elapsed(),
+                ttl: entry.ttl,
+                size: entry.data.len(),
+                refreshable: self.refreshers.contains_key(key),
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let entries = self
+            .db
+            .iter()
+            .map(|(key, entry)| SnapshotEntry {
+                key: key.clone(),
+                data: entry.data.clone(),
+                age: entry.inserted_at.elapsed().as_secs(),
+                ttl: entry.ttl.as_secs(),
+            })
+            .collect();
+        Snapshot {
+            version: SNAPSHOT_VERSION.to_string(),
+            schema: SCHEMA_VERSION,
+            entries,
+        }
+    }
+
+    /// load entries of `snapshot`, expired ones are skipped
+    fn restore(&mut self, snapshot: Snapshot) -> usize {
+        let now = Instant::now();
+        let mut restored = 0;
+        for entry in snapshot.entries {
+            let age = Duration::from_secs(entry.age);
+            let ttl = Duration::from_secs(entry.ttl);
+            if age > self.max_age(ttl) {
+                continue;
+            }
+            let entry_data = CacheEntry {
+                data: entry.data,
+                ttl,
+                inserted_at: now.checked_sub(age).unwrap_or(now),
+                last_access: now,
+            };
+            self.insert_entry(entry.key, entry_data);
+            restored += 1;
+        }
+        restored
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.db.len(),
+            ..self.stats
+        }
+    }
+
+    pub fn stale_ttl(&self) -> Duration {
+        self.stale_ttl
+    }
+
+    /// stale data is kept until this age
+    pub fn max_age(&self, ttl: Duration) -> Duration {
+        ttl + self.stale_ttl
+    }
+
+    fn inflight_lock(&mut self, key: &str) -> Arc<Mutex<InflightError>> {
+        self.inflight.entry(key.to_string()).or_default().clone()
+    }
+
+    /// forget the lock of `key` when no one else is waiting on it
+    fn release_inflight(&mut self, key: &str) {
+        if self
+            .inflight
+            .get(key)
+            .is_some_and(|i| Arc::strong_count(i) == 1)
+        {
+            self.inflight.remove(key);
+        }
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+}
+
+/// load the snapshot at `path` into `cache`, return the count of loaded entries
+pub async fn load_snapshot(cache: &SharedCache, path: &Path) -> Result<usize> {
+    let encoded = match fs::read(path).await {
+        Ok(i) => i,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let (snapshot, _): (Snapshot, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard())?;
+    if snapshot.version != SNAPSHOT_VERSION || snapshot.schema != SCHEMA_VERSION {
+        return Err(eyre!(
+            "snapshot version {} schema {} does not match {} schema {}",
+            snapshot.version,
+            snapshot.schema,
+            SNAPSHOT_VERSION,
+            SCHEMA_VERSION
+        ));
+    }
+    Ok(cache.write().unwrap().restore(snapshot))
+}
+
+/// write all entries of `cache` to `path`, replace the old snapshot atomically
+pub async fn save_snapshot(cache: &SharedCache, path: &Path) -> Result<()> {
+    let snapshot = cache.read().unwrap().snapshot();
+    let count = snapshot.entries.len();
+    let encoded = bincode::encode_to_vec(snapshot, bincode::config::standard())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, encoded).await?;
+    fs::rename(&tmp_path, path).await?;
+    info!("[Cache][SAVE] {} entries to {}", count, path.display());
+    Ok(())
+}
+
+/// evict expired entries and save the snapshot every `interval` in background
+pub fn spawn_cleanup(
+    cache: SharedCache,
+    interval: Duration,
+    snapshot_path: Option<PathBuf>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(interval.max(Duration::from_secs(1)));
+        // the first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let evicted = cache.write().unwrap().evict_expired();
+            if evicted > 0 {
+                info!("[Cache][CLEANUP] {} expired entries evicted", evicted);
+            }
+            if let Some(path) = &snapshot_path {
+                if let Err(e) = save_snapshot(&cache, path).await {
+                    warn!("[Cache] failed to save snapshot: {}", e);
+                }
+            }
+        }
+    })
+}
+
+/// keys of different data types never collide
+fn full_key<T>(key: &str) -> String {
+    format!("{}__{}", type_name::<T>(), key)
+}
+
+/// split a full key into the short type name and the key
+fn split_key(full_key: &str) -> (&str, &str) {
+    let (type_name, key) = full_key.split_once("__").unwrap_or(("", full_key));
+    (type_name.rsplit("::").next().unwrap_or(type_name), key)
 }
 
+/// corrupt or incompatible data is discarded as missing
 pub fn cache_get<T>(cache: &SharedCache, key: &str) -> Option<T>
 where
     T: bincode::Decode,
 {
-    let new_key = format!("{}__{}", type_name::<T>(), key);
-    cache.read().unwrap().db.get(&new_key).map(|i| {
-        bincode::decode_from_slice(i, bincode::config::standard())
-            .unwrap()
-            .0
-    })
+    let new_key = full_key::<T>(key);
+    let mut store = cache.write().unwrap();
+    let encoded = store.get(&new_key)?;
+    let decoded = bincode::decode_from_slice(encoded, bincode::config::standard());
+    match decoded {
+        Ok((data, len)) if len == encoded.len() => Some(data),
+        Ok(_) => {
+            warn!("[Cache][DISCARD] {}: trailing bytes", new_key);
+            store.remove(&new_key);
+            None
+        }
+        Err(e) => {
+            warn!("[Cache][DISCARD] {}: {}", new_key, e);
+            store.remove(&new_key);
+            None
+        }
+    }
 }
 
-pub fn cache_set<T>(cache: SharedCache, key: &str, input: T)
+pub fn cache_set<T>(cache: SharedCache, key: &str, ttl: Duration, input: T)
 where
     T: bincode::Encode,
 {
-    let new_key = format!("{}__{}", type_name::<T>(), key);
+    let new_key = full_key::<T>(key);
     let encoded = bincode::encode_to_vec(input, bincode::config::standard()).unwrap();
-    cache.write().unwrap().db.insert(new_key, encoded);
+    cache.write().unwrap().insert(new_key, encoded, ttl);
 }
 
 pub fn list_keys(cache: &SharedCache) -> Vec<String> {
@@ -54,31 +419,371 @@ pub fn list_keys(cache: &SharedCache) -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
-pub async fn get_or_update<T, F, Fut>(db: SharedCache, key: &str, func: F) -> T
+/// the inflight lock of a key, released when dropped, also when the request
+/// holding it is cancelled
+struct InflightGuard {
+    db: SharedCache,
+    key: String,
+    guard: Option<OwnedMutexGuard<InflightError>>,
+}
+
+impl InflightGuard {
+    async fn lock(db: &SharedCache, key: &str) -> Self {
+        let lock = db.write().unwrap().inflight_lock(key);
+        Self {
+            db: db.clone(),
+            key: key.to_string(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+
+    /// `None` if a fetch of `key` is already running
+    fn try_lock(db: &SharedCache, key: &str) -> Option<Self> {
+        let lock = db.write().unwrap().inflight_lock(key);
+        Some(Self {
+            db: db.clone(),
+            key: key.to_string(),
+            guard: Some(lock.try_lock_owned().ok()?),
+        })
+    }
+
+    /// error of the fetch we were waiting on
+    fn error<E: Clone + 'static>(&self) -> Option<E> {
+        self.guard
+            .as_deref()?
+            .as_ref()?
+            .downcast_ref::<E>()
+            .cloned()
+    }
+
+    fn set_error<E: Send + Sync + 'static>(&mut self, error: E) {
+        if let Some(guard) = self.guard.as_deref_mut() {
+            *guard = Some(Arc::new(error));
+        }
+    }
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        if let Ok(mut db) = self.db.write() {
+            db.release_inflight(&self.key);
+        }
+    }
+}
+
+/// return cached data younger than `ttl`, otherwise call `func` and cache the result:
+/// - stale data is returned right away and refreshed in background
+/// - concurrent calls for a missing key wait for one shared fetch
+///
+/// errors are never cached, older data not evicted yet is returned instead if any,
+/// calls that waited on a failed fetch get its error without fetching again
+pub async fn get_or_update<T, E, F, Fut>(
+    db: SharedCache,
+    key: &str,
+    ttl: Duration,
+    func: F,
+) -> Result<T, E>
 where
-    T: Clone + Debug + Decode + Encode + MonitorTime,
-    F: Fn() -> Fut,
-    Fut: Future<Output = T>,
+    T: Clone + Debug + Decode + Encode + MonitorTime + Send + 'static,
+    E: Clone + Debug + Send + Sync + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
 {
     let value_type = type_name::<T>();
-    let cached_data: Option<T> = cache_get(&db, key);
-    let data = if let Some(d) = cached_data {
-        if d.create_at().elapsed().unwrap() > TIMEOUT_SECS {
-            let new_data = func().await;
-            cache_set(db, key, new_data.clone());
-            info!("[Cache][UPDATE] {}: {}", value_type, key);
-            new_data
-        } else {
+    let new_key = full_key::<T>(key);
+    let func = Arc::new(func);
+    db.write()
+        .unwrap()
+        .refreshers
+        .entry(new_key.clone())
+        .or_insert_with(|| refresher(key.to_string(), ttl, func.clone()));
+    let max_age = db.read().unwrap().max_age(ttl);
+    let age_of = |data: &T| data.create_at().elapsed().unwrap_or_default();
+
+    if let Some(d) = cache_get::<T>(&db, key) {
+        if age_of(&d) <= ttl {
+            info!("[Cache][GET] {}: {}", value_type, key);
+            trace!("{:?}", d);
+            return Ok(d);
+        }
+        if age_of(&d) <= max_age {
+            info!("[Cache][STALE] {}: {}", value_type, key);
+            spawn_refresh(db, key.to_string(), ttl, func);
+            return Ok(d);
+        }
+    }
+
+    let mut inflight = InflightGuard::lock(&db, &new_key).await;
+    // filled or failed by the fetch we were waiting on
+    let fresh = cache_get::<T>(&db, key).filter(|d| age_of(d) <= ttl);
+    let result = match (fresh, inflight.error::<E>()) {
+        (Some(d), _) => {
             info!("[Cache][GET] {}: {}", value_type, key);
-            d
+            Ok(d)
         }
-    } else {
-        let new_data = func().await;
-        cache_set(db, key, new_data.clone());
-        info!("[Cache][SET] {}: {}", value_type, key);
-        new_data
+        (None, Some(e)) => {
+            info!("[Cache][FAILED] {}: {}", value_type, key);
+            Err(e)
+        }
+        (None, None) => match func().await {
+            Ok(new_data) => {
+                cache_set(db.clone(), key, ttl, new_data.clone());
+                info!("[Cache][SET] {}: {}", value_type, key);
+                Ok(new_data)
+            }
+            // e.g. the rate limit is exhausted, data not evicted yet beats an error
+            Err(e) => match cache_get::<T>(&db, key) {
+                Some(d) => {
+                    warn!(
+                        "[Cache][STALE] {}: {} fetch failed: {:?}",
+                        value_type, key, e
+                    );
+                    Ok(d)
+                }
+                None => {
+                    inflight.set_error(e.clone());
+                    Err(e)
+                }
+            },
+        },
     };
-    trace!("{:?}", data);
+    drop(inflight);
+    trace!("{:?}", result.as_ref().ok());
+
+    result
+}
+
+/// refresh `key` with `func` unless a fetch of it is already running
+fn spawn_refresh<T, E, F, Fut>(db: SharedCache, key: String, ttl: Duration, func: Arc<F>)
+where
+    T: Encode + Send + 'static,
+    E: Debug + Send + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+{
+    let Some(inflight) = InflightGuard::try_lock(&db, &full_key::<T>(&key)) else {
+        return;
+    };
+    tokio::spawn(async move {
+        let value_type = type_name::<T>();
+        match func().await {
+            Ok(new_data) => {
+                cache_set(db.clone(), &key, ttl, new_data);
+                info!("[Cache][UPDATE] {}: {}", value_type, key);
+            }
+            Err(e) => warn!("[Cache][UPDATE] {}: {} failed: {:?}", value_type, key, e),
+        }
+        drop(inflight);
+    });
+}
 
-    data
+fn refresher<T, E, F, Fut>(key: String, ttl: Duration, func: Arc<F>) -> Refresher
+where
+    T: Encode + Send + 'static,
+    E: Debug + Send + 'static,
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+{
+    Refresher(Arc::new(move |db| {
+        let (key, func) = (key.clone(), func.clone());
+        Box::pin(async move {
+            let new_data = func().await.map_err(|e| format!("{e:?}"))?;
+            cache_set(db, &key, ttl, new_data);
+            info!("[Cache][REFRESH] {}: {}", type_name::<T>(), key);
+            Ok(())
+        })
+    }))
+}
+
+/// fetch the data of `full_key` again right away, waiting for a running fetch first,
+/// `None` if it was not requested since start
+pub async fn refresh(db: &SharedCache, full_key: &str) -> Option<Result<(), String>> {
+    let refresher = db.read().unwrap().refreshers.get(full_key).cloned()?;
+    let inflight = InflightGuard::lock(db, full_key).await;
+    let result = (refresher.0)(db.clone()).await;
+    drop(inflight);
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::utils::SystemTimeWrapper;
+
+    #[derive(Debug, Clone, Default, Decode, Encode)]
+    struct Data {
+        __create_at: SystemTimeWrapper,
+    }
+
+    impl MonitorTime for Data {
+        fn create_at(&self) -> SystemTimeWrapper {
+            self.__create_at
+        }
+    }
+
+    #[test]
+    fn test_evict_lru() {
+        let mut cache = CacheStore::new(&CacheConfig {
+            max_entries: 3,
+            ..Default::default()
+        });
+        let ttl = Duration::from_secs(60);
+        // apart in time, so the access order is the lru order
+        let insert = |cache: &mut CacheStore, key: &str| {
+            std::thread::sleep(Duration::from_millis(2));
+            cache.insert(key.to_string(), vec![], ttl);
+        };
+        insert(&mut cache, "a");
+        insert(&mut cache, "b");
+        insert(&mut cache, "c");
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(cache.get("a").is_some());
+
+        insert(&mut cache, "d");
+        assert!(cache.get("b").is_none());
+        insert(&mut cache, "e");
+        assert!(cache.get("c").is_none());
+        let keys: Vec<_> = cache.entries().into_iter().map(|i| i.key).collect();
+        assert_eq!(keys, ["a", "d", "e"]);
+        assert_eq!(cache.stats().evictions, 2);
+
+        // replacing a key of a full cache evicts nothing
+        insert(&mut cache, "a");
+        assert_eq!(cache.stats().entries, cache.max_entries());
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_evict_expired() {
+        let mut cache = CacheStore::new(&CacheConfig {
+            stale_ttl: Duration::from_millis(10),
+            ..Default::default()
+        });
+        cache.insert("old".to_string(), vec![], Duration::ZERO);
+        cache.insert("new".to_string(), vec![], Duration::from_secs(60));
+        // stale data is kept until `stale_ttl` past its ttl
+        assert_eq!(cache.evict_expired(), 0);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.evict_expired(), 1);
+        assert!(cache.get("new").is_some());
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_update_coalesce() {
+        let cache = SharedCache::default();
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let mut handles = vec![];
+        for _ in 0..10 {
+            let (cache, fetches) = (cache.clone(), fetches.clone());
+            handles.push(tokio::spawn(async move {
+                get_or_update(cache, "light4", Duration::from_secs(60), move || {
+                    let fetches = fetches.clone();
+                    async move {
+                        fetches.fetch_add(1, Ordering::SeqCst);
+                        time::sleep(Duration::from_millis(50)).await;
+                        Ok::<_, ()>(Data::default())
+                    }
+                })
+                .await
+            }));
+        }
+        for handle in handles {
+            assert!(handle.await.unwrap().is_ok());
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_update_share_error() {
+        let cache = SharedCache::default();
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let mut handles = vec![];
+        for _ in 0..10 {
+            let (cache, fetches) = (cache.clone(), fetches.clone());
+            handles.push(tokio::spawn(async move {
+                get_or_update(cache, "ghost", Duration::from_secs(60), move || {
+                    let fetches = fetches.clone();
+                    async move {
+                        fetches.fetch_add(1, Ordering::SeqCst);
+                        time::sleep(Duration::from_millis(50)).await;
+                        Err::<Data, _>("not found".to_string())
+                    }
+                })
+                .await
+            }));
+        }
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap_err(), "not found");
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(cache.read().unwrap().inflight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_or_update_cancel() {
+        let cache = SharedCache::default();
+        let fetch = |cache: SharedCache| {
+            get_or_update(cache, "light4", Duration::from_secs(60), || async {
+                time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, ()>(Data::default())
+            })
+        };
+        // the client disconnects while its request holds the lock
+        let handle = tokio::spawn(fetch(cache.clone()));
+        time::sleep(Duration::from_millis(10)).await;
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+        assert!(cache.read().unwrap().inflight.is_empty());
+
+        assert!(fetch(cache.clone()).await.is_ok());
+        assert!(cache.read().unwrap().inflight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_roundtrip() {
+        let path = std::env::temp_dir().join(format!("mine-stats-{}.cache", std::process::id()));
+        let cache = SharedCache::default();
+        let ttl = Duration::from_secs(60);
+        cache_set(cache.clone(), "light4", ttl, 42_u32);
+        cache
+            .write()
+            .unwrap()
+            .insert(full_key::<String>("broken"), vec![255], ttl);
+        save_snapshot(&cache, &path).await.unwrap();
+
+        let restored = SharedCache::default();
+        assert_eq!(load_snapshot(&restored, &path).await.unwrap(), 2);
+        assert_eq!(cache_get::<u32>(&restored, "light4"), Some(42));
+        // corrupt data is discarded instead of panicking
+        assert_eq!(cache_get::<String>(&restored, "broken"), None);
+        assert_eq!(restored.read().unwrap().stats().entries, 1);
+
+        // an older layout of the cached types is not loaded
+        let snapshot = Snapshot {
+            schema: SCHEMA_VERSION - 1,
+            ..cache.read().unwrap().snapshot()
+        };
+        let encoded = bincode::encode_to_vec(snapshot, bincode::config::standard()).unwrap();
+        std::fs::write(&path, encoded).unwrap();
+        assert!(load_snapshot(&SharedCache::default(), &path).await.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_cache_get_trailing_bytes() {
+        let cache = SharedCache::default();
+        let ttl = Duration::from_secs(60);
+        // a `u8` followed by a field it does not have
+        cache
+            .write()
+            .unwrap()
+            .insert(full_key::<u8>("light4"), vec![1, 2], ttl);
+        assert_eq!(cache_get::<u8>(&cache, "light4"), None);
+        assert_eq!(cache.read().unwrap().stats().entries, 0);
+    }
 }
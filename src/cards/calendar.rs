@@ -0,0 +1,313 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use svg::{
+    node::{
+        self,
+        element::{Group, Rectangle, Text, Title},
+    },
+    Document, Node,
+};
+
+use super::{icons::Icon, style::mix_colors, CardBuilder, NumberFormatter};
+use crate::{config::Theme, github::calendar::ContributionCalendar, utils::current_year};
+
+const CELL_SIZE: u16 = 10;
+const CELL_STEP: u16 = 13;
+const CARD_PADDING: u16 = 25;
+const WEEKDAY_LABEL_WIDTH: u16 = 28;
+const MONTH_LABEL_HEIGHT: u16 = 15;
+/// the total and the legend fit below calendars of a few weeks
+const MIN_CARD_WIDTH: u16 = 350;
+/// color ratio between `bg` and `title` of each level
+const LEVEL_RATIOS: [f32; 5] = [0.12, 0.35, 0.55, 0.78, 1.];
+const FIRST_CONTRIBUTION_YEAR: i32 = 2008;
+
+/// options of the calendar card, parsed from query parameters
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CalendarCardOptions {
+    /// calendar year, last year if not set
+    pub year: Option<i32>,
+}
+
+impl CalendarCardOptions {
+    /// check values out of range, return a message for user if any
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(year) = self.year {
+            let current = current_year();
+            if !(FIRST_CONTRIBUTION_YEAR..=current).contains(&year) {
+                return Err(format!(
+                    "year must be between {FIRST_CONTRIBUTION_YEAR} and {current}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 0 for no contributions, 1 to 4 for quartiles of the busiest day
+fn contribution_level(count: i64, max_count: i64) -> usize {
+    if count <= 0 || max_count <= 0 {
+        return 0;
+    }
+    ((count * 4 + max_count - 1) / max_count).clamp(1, 4) as usize
+}
+
+fn create_month_labels(calendar: &ContributionCalendar) -> Group {
+    let mut g = Group::new();
+    let mut last_month = None;
+    for (idx, week) in calendar.weeks.iter().enumerate() {
+        let Some(date) = week
+            .first()
+            .and_then(|day| NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        // skip the first partial month, the label would overlap the next one
+        if last_month != Some(date.month()) && (idx > 0 || date.day() <= 7) {
+            g.append(
+                Text::new()
+                    .set("class", "label")
+                    .set("x", idx as u16 * CELL_STEP)
+                    .set("y", 0)
+                    .add(node::Text::new(date.format("%b").to_string())),
+            );
+        }
+        last_month = Some(date.month());
+    }
+    g
+}
+
+fn create_weekday_labels() -> Group {
+    let mut g = Group::new();
+    for (weekday, label) in [(1, "Mon"), (3, "Wed"), (5, "Fri")] {
+        g.append(
+            Text::new()
+                .set("class", "label")
+                .set("x", 0)
+                .set("y", MONTH_LABEL_HEIGHT + weekday * CELL_STEP + 9)
+                .add(node::Text::new(label)),
+        );
+    }
+    g
+}
+
+fn create_cell(x: u16, y: u16, color: &str) -> Rectangle {
+    Rectangle::new()
+        .set("x", x)
+        .set("y", y)
+        .set("rx", 2)
+        .set("ry", 2)
+        .set("width", CELL_SIZE)
+        .set("height", CELL_SIZE)
+        .set("fill", color)
+}
+
+fn create_legend(colors: &[String], x: u16, y: u16) -> Group {
+    let mut g = Group::new()
+        .set("transform", format!("translate({x}, {y})"))
+        .add(
+            Text::new()
+                .set("class", "label")
+                .set("x", -6)
+                .set("y", 9)
+                .set("text-anchor", "end")
+                .add(node::Text::new("Less")),
+        );
+    for (idx, color) in colors.iter().enumerate() {
+        g.append(create_cell(idx as u16 * CELL_STEP, 0, color));
+    }
+    g.add(
+        Text::new()
+            .set("class", "label")
+            .set("x", colors.len() as u16 * CELL_STEP + 3)
+            .set("y", 9)
+            .add(node::Text::new("More")),
+    )
+}
+
+pub fn form_calendar_card(
+    calendar: ContributionCalendar,
+    numbers: NumberFormatter,
+    theme: Theme,
+) -> Document {
+    let colors: Vec<String> = LEVEL_RATIOS
+        .iter()
+        .map(|ratio| mix_colors(&theme.bg, &theme.title, *ratio))
+        .collect();
+    let max_count = calendar
+        .weeks
+        .iter()
+        .flatten()
+        .map(|day| day.count)
+        .max()
+        .unwrap_or(0);
+
+    let grid_width = calendar.weeks.len() as u16 * CELL_STEP;
+    let width = (CARD_PADDING * 2 + WEEKDAY_LABEL_WIDTH + grid_width).max(MIN_CARD_WIDTH);
+    let grid_bottom = MONTH_LABEL_HEIGHT + 7 * CELL_STEP;
+    let height = 55 + grid_bottom + 40;
+
+    let mut grid = Group::new()
+        .set("data-testid", "calendar-grid")
+        .set("transform", format!("translate(0, {MONTH_LABEL_HEIGHT})"));
+    for (week_idx, week) in calendar.weeks.iter().enumerate() {
+        for day in week {
+            let color = &colors[contribution_level(day.count, max_count)];
+            let cell = create_cell(
+                week_idx as u16 * CELL_STEP,
+                day.weekday as u16 * CELL_STEP,
+                color,
+            )
+            .add(Title::new().add(node::Text::new(format!(
+                "{} contributions on {}",
+                numbers.format(day.count),
+                day.date
+            ))));
+            grid.append(cell);
+        }
+    }
+
+    let calendar_node = Group::new()
+        .set(
+            "transform",
+            format!("translate({}, 0)", CARD_PADDING + WEEKDAY_LABEL_WIDTH),
+        )
+        .add(create_month_labels(&calendar).set("transform", "translate(0, 8)"))
+        .add(grid);
+
+    let period = calendar
+        .year
+        .map(|y| format!("in {y}"))
+        .unwrap_or("in the last year".to_string());
+    let total = Text::new()
+        .set("class", "label")
+        .set("x", CARD_PADDING + WEEKDAY_LABEL_WIDTH)
+        .set("y", grid_bottom + 22)
+        .add(node::Text::new(format!(
+            "{} contributions {period}",
+            numbers.format(calendar.total_contributions)
+        )));
+    let legend = create_legend(
+        &colors,
+        width - CARD_PADDING - (colors.len() as u16 * CELL_STEP) - 30,
+        grid_bottom + 13,
+    );
+
+    let body = Group::new()
+        .add(create_weekday_labels().set("transform", format!("translate({CARD_PADDING}, 0)")))
+        .add(calendar_node)
+        .add(total)
+        .add(legend);
+
+    let css = format!(
+        r#"
+      .label {{ font: 400 10px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}
+      .icon {{ fill: {} }}
+    "#,
+        theme.text, theme.icon
+    );
+    let title = format!("{}'s Contributions", &calendar.name);
+    CardBuilder::default()
+        .with_width(width)
+        .with_height(height)
+        .with_title(&title)
+        .with_title_icon(Icon::Contribs)
+        .with_theme(theme)
+        .with_css(css)
+        .with_a11y_title(&title)
+        .with_a11y_desc(format!(
+            "{} contributions {period}",
+            numbers.format(calendar.total_contributions)
+        ))
+        .build()
+        .render(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use resvg::usvg;
+
+    use super::*;
+    use crate::github::calendar::ContributionDay;
+
+    #[test]
+    fn test_validate() {
+        let validate = |year| CalendarCardOptions { year }.validate();
+        assert!(validate(None).is_ok());
+        assert!(validate(Some(FIRST_CONTRIBUTION_YEAR)).is_ok());
+        assert!(validate(Some(current_year())).is_ok());
+        assert!(validate(Some(FIRST_CONTRIBUTION_YEAR - 1)).is_err());
+        assert!(validate(Some(current_year() + 1)).is_err());
+    }
+
+    #[test]
+    fn test_contribution_level() {
+        assert_eq!(contribution_level(0, 8), 0);
+        assert_eq!(contribution_level(3, 0), 0);
+        assert_eq!(contribution_level(1, 8), 1);
+        assert_eq!(contribution_level(2, 8), 1);
+        assert_eq!(contribution_level(3, 8), 2);
+        assert_eq!(contribution_level(8, 8), 4);
+    }
+
+    #[test]
+    fn test_form_calendar_card() {
+        // two weeks from Sunday 2023-01-01, the busiest day last
+        let weeks = (0..2)
+            .map(|week| {
+                (0..7)
+                    .map(|weekday| ContributionDay {
+                        date: format!("2023-01-{:02}", week * 7 + weekday + 1),
+                        count: (week * 7 + weekday) as i64,
+                        weekday,
+                    })
+                    .collect()
+            })
+            .collect();
+        let calendar = ContributionCalendar {
+            login: "light4".to_string(),
+            name: "Light".to_string(),
+            year: Some(2023),
+            total_contributions: 91,
+            weeks,
+            ..Default::default()
+        };
+        let theme = Theme::default();
+        let svg =
+            form_calendar_card(calendar, NumberFormatter::default(), theme.clone()).to_string();
+
+        assert!(
+            svg.contains(&format!(r#"width="{MIN_CARD_WIDTH}""#)),
+            "{svg}"
+        );
+        assert_eq!(svg.matches(" contributions on 2023-01-").count(), 14);
+        assert!(svg.contains("0 contributions on 2023-01-01"));
+        assert!(svg.contains("13 contributions on 2023-01-14"));
+        assert!(svg.contains("91 contributions in 2023"));
+        assert!(svg.contains("Jan"));
+        // one cell of each level in the legend, days 10 to 13 are the busiest
+        let cells = |ratio| {
+            let color = mix_colors(&theme.bg, &theme.title, ratio);
+            svg.matches(&format!(r#"fill="{color}""#)).count()
+        };
+        assert_eq!(cells(LEVEL_RATIOS[0]), 2);
+        assert_eq!(cells(LEVEL_RATIOS[4]), 5);
+    }
+
+    #[test]
+    fn test_escape_name() {
+        let calendar = ContributionCalendar {
+            name: "a<b&c".to_string(),
+            ..Default::default()
+        };
+        let svg =
+            form_calendar_card(calendar, NumberFormatter::default(), Theme::default()).to_string();
+        let options = usvg::Options::default();
+        assert!(
+            usvg::Tree::from_str(&svg, &options, &usvg::fontdb::Database::new()).is_ok(),
+            "{svg}"
+        );
+        assert!(svg.contains("a&lt;b&amp;c&#39;s Contributions"), "{svg}");
+    }
+}
@@ -0,0 +1,59 @@
+use svg::{
+    node::{
+        self,
+        element::{Group, TSpan, Text},
+    },
+    Document, Node,
+};
+
+use super::{wrap_text_multiline, CardBuilder};
+use crate::{config::Theme, utils::encode_html};
+
+const CARD_WIDTH: u16 = 495;
+const MESSAGE_LINE_WIDTH: usize = 45;
+const MESSAGE_MAX_LINES: usize = 2;
+const LINE_HEIGHT: u16 = 20;
+
+pub fn form_error_card(message: &str, hint: &str, theme: Theme) -> Document {
+    let lines = wrap_text_multiline(message, MESSAGE_LINE_WIDTH, MESSAGE_MAX_LINES);
+    let hint_y = 10 + lines.len() as u16 * LINE_HEIGHT;
+    let mut text = Text::new()
+        .set("data-testid", "message")
+        .set("class", "message")
+        .set("x", 25)
+        .set("y", 10);
+    for (idx, line) in lines.into_iter().enumerate() {
+        text.append(
+            TSpan::new()
+                .set("x", 25)
+                .set("dy", if idx == 0 { 0 } else { LINE_HEIGHT })
+                .add(node::Text::new(encode_html(&line))),
+        );
+    }
+    let hint = Text::new()
+        .set("data-testid", "hint")
+        .set("class", "hint")
+        .set("x", 25)
+        .set("y", hint_y)
+        .add(node::Text::new(encode_html(hint)));
+    let body = Group::new().add(text).add(hint);
+
+    let css = format!(
+        r#"
+      .message {{ font: 600 16px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}
+      .hint {{ font: 400 12px 'Segoe UI', Ubuntu, sans-serif; fill: {}; opacity: 0.8 }}
+      .icon {{ fill: {} }}
+    "#,
+        theme.text, theme.text, theme.icon
+    );
+    CardBuilder::default()
+        .with_width(CARD_WIDTH)
+        .with_height(hint_y + 85)
+        .with_title("Something went wrong!")
+        .with_theme(theme)
+        .with_css(css)
+        .with_a11y_title("Something went wrong!")
+        .with_a11y_desc(message)
+        .build()
+        .render(body)
+}
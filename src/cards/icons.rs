@@ -3,6 +3,7 @@ use std::str::FromStr;
 use once_cell::sync::Lazy;
 use svg::node::element::{path::Data, Path};
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[non_exhaustive]
 pub enum Icon {
@@ -0,0 +1,139 @@
+//! estimated text width in the card fonts, to lay out cards without a renderer
+//!
+//! widths of printable ascii are Helvetica metrics, close to `Segoe UI` and
+//! `Ubuntu`, in 1/1000 em
+
+/// ` ` to `~`
+const REGULAR: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278,
+    278, // ` ` - `/`
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584,
+    556, // `0` - `?`
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722,
+    778, // `@` - `O`
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469,
+    556, // `P` - `_`
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556,
+    556, // `` ` `` - `o`
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // `p` - `~`
+];
+
+/// ` ` to `~`, font weight 600 and above
+const BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278,
+    278, // ` ` - `/`
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584,
+    611, // `0` - `?`
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722,
+    778, // `@` - `O`
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584,
+    556, // `P` - `_`
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611,
+    611, // `` ` `` - `o`
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, // `p` - `~`
+];
+
+/// CJK and emoji are about square
+const WIDE: u16 = 1000;
+
+const ELLIPSIS: char = '…';
+
+/// width of `c` in 1/1000 em, `None` if it has no width of its own
+fn char_width(c: char, bold: bool) -> Option<u16> {
+    let widths = if bold { &BOLD } else { &REGULAR };
+    match c as u32 {
+        0x20..=0x7e => Some(widths[c as usize - 0x20]),
+        // combining marks, zero width spaces and joiners, variation selectors,
+        // emoji skin tones and tags
+        0x0300..=0x036f
+        | 0x200b..=0x200f
+        | 0xfe00..=0xfe0f
+        | 0x1f3fb..=0x1f3ff
+        | 0xe0020..=0xe007f => None,
+        // hangul jamo, cjk, hangul syllables, compatibility ideographs, fullwidth forms
+        0x1100..=0x115f
+        | 0x2e80..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x20000..=0x3fffd => Some(WIDE),
+        // symbols and emoji
+        0x2600..=0x27bf | 0x1f000..=0x1faff => Some(WIDE),
+        // `…`
+        0x2026 => Some(1000),
+        // other letters, as wide as an average lowercase letter
+        _ => Some(widths[b'n' as usize - 0x20]),
+    }
+}
+
+/// widths of the chars of `text` in px, the parts of an emoji sequence after
+/// a zero width joiner take no space
+fn char_widths(text: &str, font_size: f32, bold: bool) -> impl Iterator<Item = (char, f32)> + '_ {
+    let mut joined = false;
+    text.chars().map(move |c| {
+        let width = if joined {
+            0
+        } else {
+            char_width(c, bold).unwrap_or(0)
+        };
+        joined = c == '\u{200d}';
+        (c, width as f32 * font_size / 1000.)
+    })
+}
+
+/// estimated width in px of `text` rendered at `font_size`
+pub fn measure_text(text: &str, font_size: f32, bold: bool) -> f32 {
+    char_widths(text, font_size, bold)
+        .map(|(_, width)| width)
+        .sum()
+}
+
+/// cut `text` to fit in `max_width` px, ending with an ellipsis if it is cut
+pub fn truncate_text(text: &str, max_width: f32, font_size: f32, bold: bool) -> String {
+    if measure_text(text, font_size, bold) <= max_width {
+        return text.to_string();
+    }
+    let max_width = max_width - measure_text(&ELLIPSIS.to_string(), font_size, bold);
+    let mut width = 0.;
+    let mut result = String::new();
+    for (c, char_width) in char_widths(text, font_size, bold) {
+        width += char_width;
+        if width > max_width {
+            break;
+        }
+        result.push(c);
+    }
+    let mut result = result.trim_end().to_string();
+    result.push(ELLIPSIS);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_text() {
+        assert_eq!(measure_text("", 14., false), 0.);
+        assert!((measure_text("Hi", 10., false) - 9.44).abs() < 0.01);
+        assert!(measure_text("Hi", 10., true) > measure_text("Hi", 10., false));
+        // cjk and emoji are about square
+        assert_eq!(measure_text("你好", 10., false), 20.);
+        assert_eq!(measure_text("👍🏽", 10., false), 10.);
+        // a family, joined by zero width joiners
+        assert_eq!(measure_text("👩\u{200d}👩\u{200d}👧", 10., false), 10.);
+    }
+
+    #[test]
+    fn test_truncate_text() {
+        assert_eq!(truncate_text("light4", 100., 18., true), "light4");
+        let title = "A Very Long Name Of Someone's GitHub Stats";
+        let truncated = truncate_text(title, 200., 18., true);
+        assert!(truncated.ends_with(ELLIPSIS), "{truncated}");
+        assert!(measure_text(&truncated, 18., true) <= 200.);
+        assert!(title.starts_with(truncated.trim_end_matches(ELLIPSIS)));
+        assert_eq!(truncate_text("你好世界", 30., 10., false), "你好…");
+    }
+}
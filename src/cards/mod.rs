@@ -7,19 +7,49 @@ use svg::{
 };
 use tracing::trace;
 
+mod calendar;
+mod error;
 mod icons;
+mod metrics;
+mod number;
+mod png;
 mod progress;
+mod repo;
 mod stats;
+mod streak;
 mod style;
 mod top_langs;
 
-pub use stats::form_stats_card;
-pub use top_langs::form_top_langs_card;
+pub use calendar::{form_calendar_card, CalendarCardOptions};
+pub use error::form_error_card;
+use icons::Icon;
+use metrics::{measure_text, truncate_text};
+pub use number::{NumberFormat, NumberFormatter};
+pub use png::{png_scale, png_size, rasterize};
+pub use repo::form_repo_card;
+pub use stats::{form_stats_card, StatsCardOptions};
+pub use streak::form_streak_card;
+pub use top_langs::{form_top_langs_card, use_languages, TopLangsCardOptions};
 
-use crate::config::{Theme, DEFAULT};
+use crate::{
+    config::{Theme, DEFAULT},
+    utils::encode_html,
+};
 
-#[derive(Debug, Clone, Default)]
-pub struct Color {}
+const PADDING_X: usize = 25;
+/// `.header`
+const TITLE_FONT_SIZE: f32 = 18.;
+/// the title text follows its icon
+const TITLE_ICON_SIZE: u16 = 16;
+const TITLE_GAP: u16 = 9;
+
+/// card width to show `title` in full, `u16::MAX` for titles wider than that
+pub fn title_width(title: &str) -> u16 {
+    let width = measure_text(title, TITLE_FONT_SIZE, true).ceil()
+        + (2 * PADDING_X) as f32
+        + f32::from(TITLE_ICON_SIZE + TITLE_GAP);
+    width.min(f32::from(u16::MAX)) as u16
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct Card {
@@ -29,6 +59,7 @@ pub struct Card {
     theme: Theme,
     css: String,
     title: String,
+    title_icon: Option<Icon>,
     hide_border: bool,
     hide_title: bool,
     padding_x: usize,
@@ -54,7 +85,7 @@ impl Default for CardBuilder {
                 border_radius: 4.5,
                 hide_border: false,
                 hide_title: false,
-                padding_x: 25,
+                padding_x: PADDING_X,
                 padding_y: 35,
                 animations: true,
                 ..Default::default()
@@ -76,7 +107,6 @@ impl CardBuilder {
         self
     }
 
-    #[allow(dead_code)]
     #[inline]
     pub fn with_border_radius(mut self, border_radius: f32) -> Self {
         self.inner.border_radius = border_radius;
@@ -89,6 +119,24 @@ impl CardBuilder {
         self
     }
 
+    #[inline]
+    pub fn with_title_icon(mut self, icon: Icon) -> Self {
+        self.inner.title_icon = Some(icon);
+        self
+    }
+
+    #[inline]
+    pub fn with_hide_border(mut self, hide_border: bool) -> Self {
+        self.inner.hide_border = hide_border;
+        self
+    }
+
+    #[inline]
+    pub fn with_hide_title(mut self, hide_title: bool) -> Self {
+        self.inner.hide_title = hide_title;
+        self
+    }
+
     #[inline]
     pub fn with_css<T: Into<String>>(mut self, css: T) -> Self {
         self.inner.css = css.into();
@@ -126,13 +174,18 @@ impl CardBuilder {
 }
 
 impl Card {
+    /// the title is cut with an ellipsis if it is wider than the card
     pub fn render_title(&self, title_prefix_icon: Path) -> Group {
+        let max_width = self
+            .width
+            .saturating_sub(2 * self.padding_x as u16 + TITLE_ICON_SIZE + TITLE_GAP);
+        let title = truncate_text(&self.title, max_width as f32, TITLE_FONT_SIZE, true);
         let title = Text::new()
             .set("x", 0)
             .set("y", 0)
             .set("class", "header")
             .set("data-testid", "header")
-            .add(node::Text::new(&self.title));
+            .add(node::Text::new(encode_html(&title)));
 
         let prefix_icon = SVG::new()
             .set("class", "icon")
@@ -140,8 +193,8 @@ impl Card {
             .set("y", -13)
             .set("viewBox", "0 0 16 16")
             .set("version", "1.1")
-            .set("width", 16)
-            .set("height", 16)
+            .set("width", TITLE_ICON_SIZE)
+            .set("height", TITLE_ICON_SIZE)
             .add(title_prefix_icon);
 
         let mut g = Group::new().set("data-testid", "card-title").set(
@@ -150,23 +203,24 @@ impl Card {
         );
 
         let items: Vec<Element> = vec![prefix_icon.into(), title.into()];
-        for item in flex_layout(items, 25, "") {
+        for item in flex_layout(items, &[TITLE_ICON_SIZE], TITLE_GAP, "") {
             g.append(item);
         }
         g
     }
 
+    /// the a11y title and description are escaped here, cards pass them as is
     pub fn render(&self, body: Group) -> Document {
         let a11y_title = Title::new()
             .set("id", "titleId")
-            .add(node::Text::new(&self.a11y_title));
+            .add(node::Text::new(encode_html(&self.a11y_title)));
         let a11y_desc = Description::new()
             .set("id", "descId")
-            .add(node::Text::new(&self.a11y_desc));
+            .add(node::Text::new(encode_html(&self.a11y_desc)));
         let style = Style::new(format!(
             r#"
           .header {{
-            font: 600 18px 'Segoe UI', Ubuntu, Sans-Serif;
+            font: 600 18px 'Segoe UI', Ubuntu, sans-serif;
             fill: {};
             animation: fadeInAnimation 0.8s ease-in-out forwards;
           }}
@@ -218,7 +272,7 @@ impl Card {
             ),
         );
 
-        let document = Document::new()
+        let mut document = Document::new()
             .set("width", self.width)
             .set("height", self.height)
             .set("viewBox", (0, 0, self.width, self.height))
@@ -228,9 +282,12 @@ impl Card {
             .add(a11y_title)
             .add(a11y_desc)
             .add(style)
-            .add(rect)
-            .add(self.render_title(icons::Icon::Contribs.svg_path()))
-            .add(body);
+            .add(rect);
+        if !self.hide_title {
+            document
+                .append(self.render_title(self.title_icon.unwrap_or(Icon::Contribs).svg_path()));
+        }
+        document.append(body);
 
         trace!("{}", document.to_string());
 
@@ -238,18 +295,17 @@ impl Card {
     }
 }
 
-pub fn flex_layout<T>(items: Vec<T>, gap: u16, direction: &str) -> Vec<Group>
+/// place `items` one after another, each item takes its size in `sizes` and `gap`
+pub fn flex_layout<T>(items: Vec<T>, sizes: &[u16], gap: u16, direction: &str) -> Vec<Group>
 where
     T: Into<Element>,
 {
     let mut last_size = 0;
-    // filter() for filtering out empty strings
     items
         .into_iter()
         .enumerate()
-        .map(|(_i, item)| {
-            // let size = sizes.get(i).copied().unwrap_or(0);
-            let size = 0;
+        .map(|(i, item)| {
+            let size = sizes.get(i).copied().unwrap_or(0);
             let transform = {
                 if direction == "column" {
                     format!("translate(0, {last_size})")
@@ -262,3 +318,42 @@ where
         })
         .collect()
 }
+
+/// split `text` into at most `max_lines` lines of `width` chars,
+/// the last line ends with `...` if the text is truncated
+pub fn wrap_text_multiline(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + word.chars().count() + 1 > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            last.push_str("...");
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_width() {
+        assert_eq!(title_width(""), 75);
+        assert!(title_width("light4's GitHub Stats") > 75);
+        assert_eq!(title_width(&"W".repeat(5000)), u16::MAX);
+    }
+}
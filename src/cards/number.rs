@@ -0,0 +1,174 @@
+//! numbers on cards, abbreviated or grouped by the locale
+
+use serde::Deserialize;
+
+/// `short` abbreviates large numbers like `1.2k`, `long` writes every digit,
+/// the default as cards always did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    Short,
+    #[default]
+    Long,
+}
+
+/// abbreviations of `short` numbers, smallest first
+const UNITS: [(f64, &str); 3] = [(1e3, "k"), (1e6, "M"), (1e9, "B")];
+
+/// one formatter shared by all numbers of a card
+#[derive(Debug, Clone, Copy)]
+pub struct NumberFormatter {
+    format: NumberFormat,
+    group: &'static str,
+    decimal: char,
+}
+
+impl Default for NumberFormatter {
+    fn default() -> Self {
+        Self::new(NumberFormat::default(), None)
+    }
+}
+
+impl NumberFormatter {
+    /// `locale` like `de` or `pt-BR` picks the separators, unknown locales
+    /// are written like `en`, without a locale digits are not grouped
+    pub fn new(format: NumberFormat, locale: Option<&str>) -> Self {
+        let Some(locale) = locale else {
+            return Self {
+                format,
+                group: "",
+                decimal: '.',
+            };
+        };
+        let locale = locale.to_ascii_lowercase();
+        let mut parts = locale.split(['-', '_']);
+        let language = parts.next().unwrap_or_default();
+        let region = parts.next().unwrap_or_default();
+        let (group, decimal) = match (language, region) {
+            ("de" | "it", "ch" | "li") => ("’", '.'),
+            (
+                "da" | "de" | "el" | "es" | "hr" | "id" | "it" | "nl" | "pt" | "ro" | "sl" | "sr"
+                | "tr" | "vi",
+                _,
+            ) => (".", ','),
+            (
+                "bg" | "cs" | "et" | "fi" | "fr" | "hu" | "lt" | "lv" | "nb" | "no" | "pl" | "ru"
+                | "sk" | "sv" | "uk",
+                _,
+            ) => ("\u{a0}", ','),
+            _ => (",", '.'),
+        };
+        Self {
+            format,
+            group,
+            decimal,
+        }
+    }
+
+    /// counts like stars and commits
+    pub fn format(&self, value: i64) -> String {
+        if self.format == NumberFormat::Short && value.unsigned_abs() >= 1000 {
+            // one decimal of the smallest unit that stays under 1000, so
+            // `999_950` is `1M` rather than `1000k`
+            let round = |size: f64| (value as f64 / size * 10.).round() / 10.;
+            let (size, suffix) = UNITS
+                .into_iter()
+                .find(|(size, _)| round(*size).abs() < 1000.)
+                .unwrap_or(UNITS[UNITS.len() - 1]);
+            let short = round(size);
+            let precision = if short.fract() == 0. { 0 } else { 1 };
+            return format!("{}{suffix}", self.decimal(short, precision));
+        }
+        self.group(&value.unsigned_abs().to_string(), value < 0)
+    }
+
+    /// `value` with `precision` digits after the decimal separator
+    pub fn decimal(&self, value: f64, precision: usize) -> String {
+        let text = format!("{:.precision$}", value.abs());
+        let (integer, fraction) = text.split_once('.').unwrap_or((&text, ""));
+        let mut result = self.group(integer, value < 0. && text.chars().any(|c| c > '0'));
+        if !fraction.is_empty() {
+            result.push(self.decimal);
+            result.push_str(fraction);
+        }
+        result
+    }
+
+    /// `value` out of 100, like `12.34%`
+    pub fn percent(&self, value: f64, precision: usize) -> String {
+        format!("{}%", self.decimal(value, precision))
+    }
+
+    /// put the group separator between every three of `digits`
+    fn group(&self, digits: &str, negative: bool) -> String {
+        let mut result = String::with_capacity(digits.len() * 2);
+        if negative {
+            result.push('-');
+        }
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                result.push_str(self.group);
+            }
+            result.push(c);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        // every digit and no grouping, like cards without these options
+        let default = NumberFormatter::default();
+        assert_eq!(default.format(999), "999");
+        assert_eq!(default.format(1234), "1234");
+        assert_eq!(default.format(-1_234_567), "-1234567");
+        assert_eq!(default.decimal(1234.5, 1), "1234.5");
+
+        let short = NumberFormatter::new(NumberFormat::Short, None);
+        for (value, expected) in [
+            (0, "0"),
+            (999, "999"),
+            (1000, "1k"),
+            (1234, "1.2k"),
+            (-1234, "-1.2k"),
+            (123_456, "123.5k"),
+            (999_949, "999.9k"),
+            (999_950, "1M"),
+            (3_400_000, "3.4M"),
+            (2_000_000_000, "2B"),
+        ] {
+            assert_eq!(short.format(value), expected, "{value}");
+        }
+
+        let long = NumberFormatter::new(NumberFormat::Long, Some("en"));
+        assert_eq!(long.format(999), "999");
+        assert_eq!(long.format(123_456), "123,456");
+        assert_eq!(long.format(-1_234_567), "-1,234,567");
+        let long = NumberFormatter::new(NumberFormat::Long, Some("de-DE"));
+        assert_eq!(long.format(123_456), "123.456");
+        let long = NumberFormatter::new(NumberFormat::Long, Some("de_CH"));
+        assert_eq!(long.format(123_456), "123’456");
+        let long = NumberFormatter::new(NumberFormat::Long, Some("fr"));
+        assert_eq!(long.format(123_456), "123\u{a0}456");
+        let long = NumberFormatter::new(NumberFormat::Long, Some("unknown"));
+        assert_eq!(long.format(123_456), "123,456");
+
+        let short = NumberFormatter::new(NumberFormat::Short, Some("de"));
+        assert_eq!(short.format(1234), "1,2k");
+    }
+
+    #[test]
+    fn test_percent() {
+        let en = NumberFormatter::default();
+        assert_eq!(en.percent(75., 2), "75.00%");
+        assert_eq!(en.percent(12.345, 1), "12.3%");
+        assert_eq!(en.percent(-0.01, 1), "0.0%");
+        let de = NumberFormatter::new(NumberFormat::Short, Some("de"));
+        assert_eq!(de.percent(75., 2), "75,00%");
+        assert_eq!(de.decimal(1234.5, 1), "1.234,5");
+    }
+}
@@ -0,0 +1,129 @@
+//! rasterise cards to png, for places that do not render svg
+
+use bincode::{Decode, Encode};
+use once_cell::sync::Lazy;
+use resvg::{
+    tiny_skia::{IntSize, Pixmap, Transform},
+    usvg::{self, fontdb},
+};
+
+use crate::utils::{MonitorTime, SystemTimeWrapper};
+
+/// `scale=` is clamped to this range
+const SCALE_RANGE: (f32, f32) = (0.5, 4.);
+
+/// pixels of a png at most, 16MB of rgba while rasterising
+const MAX_PIXELS: u32 = 4_000_000;
+
+/// fonts tried in order for the `sans-serif` of the cards, those with the
+/// metrics of `cards::metrics` first
+const SANS_SERIF_FAMILIES: [&str; 7] = [
+    "Segoe UI",
+    "Liberation Sans",
+    "Arial",
+    "Helvetica",
+    "Ubuntu",
+    "Noto Sans",
+    "DejaVu Sans",
+];
+
+/// animations are not played by the rasteriser, show elements in their final state,
+/// and css transforms take no angle units
+const STATIC_STYLE: &str = "<style>
+  .stagger { opacity: 1 }
+  .rank-circle { transform: rotate(-90) }
+  .rank-text { transform: translate(-5, 5) }
+</style>";
+
+/// system fonts, loading them is slow so it is done once
+static FONTS: Lazy<fontdb::Database> = Lazy::new(|| {
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    let family = SANS_SERIF_FAMILIES.into_iter().find(|family| {
+        fonts
+            .faces()
+            .any(|face| face.families.iter().any(|(name, _)| name == family))
+    });
+    if let Some(family) = family {
+        fonts.set_sans_serif_family(family);
+    }
+    fonts
+});
+
+/// a rasterised card
+#[derive(Debug, Clone, Default, Decode, Encode)]
+pub struct CardPng {
+    pub bytes: Vec<u8>,
+    pub(crate) __create_at: SystemTimeWrapper,
+}
+
+impl MonitorTime for CardPng {
+    fn create_at(&self) -> SystemTimeWrapper {
+        self.__create_at
+    }
+}
+
+/// `scale=` in range, 1 if missing
+pub fn png_scale(scale: Option<f32>) -> f32 {
+    scale
+        .filter(|i| i.is_finite())
+        .unwrap_or(1.)
+        .clamp(SCALE_RANGE.0, SCALE_RANGE.1)
+}
+
+/// size of the png of a `width` by `height` card, an error if it is over the
+/// pixel budget
+pub fn png_size(width: f32, height: f32, scale: f32) -> Result<IntSize, String> {
+    let size = IntSize::from_wh(width.ceil() as u32, height.ceil() as u32)
+        .and_then(|size| size.scale_by(scale))
+        .ok_or("invalid card size")?;
+    if u64::from(size.width()) * u64::from(size.height()) > u64::from(MAX_PIXELS) {
+        return Err(format!(
+            "png of {}x{} pixels is over the limit of {MAX_PIXELS} pixels, lower scale",
+            size.width(),
+            size.height()
+        ));
+    }
+    Ok(size)
+}
+
+/// rasterise the rendered svg of a card, `scale` 2 for HiDPI screens
+pub fn rasterize(svg: &str, scale: f32) -> Result<CardPng, String> {
+    let scale = png_scale(Some(scale));
+    let svg = match svg.rfind("</svg>") {
+        Some(end) => format!("{}{STATIC_STYLE}{}", &svg[..end], &svg[end..]),
+        None => svg.to_string(),
+    };
+    let tree =
+        usvg::Tree::from_str(&svg, &usvg::Options::default(), &FONTS).map_err(|e| e.to_string())?;
+
+    let size = png_size(tree.size().width(), tree.size().height(), scale)?;
+    let mut pixmap = Pixmap::new(size.width(), size.height()).ok_or("invalid card size")?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(CardPng {
+        bytes: pixmap.encode_png().map_err(|e| e.to_string())?,
+        __create_at: SystemTimeWrapper::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_size() {
+        assert_eq!(png_scale(None), 1.);
+        assert_eq!(png_scale(Some(f32::NAN)), 1.);
+        assert_eq!(png_scale(Some(10.)), 4.);
+
+        let size = png_size(495., 195., 2.).unwrap();
+        assert_eq!((size.width(), size.height()), (990, 390));
+        assert!(png_size(1000., 1000., 2.).is_ok());
+        assert!(png_size(1000., 1001., 2.).is_err());
+        assert!(png_size(0., 100., 1.).is_err());
+    }
+}
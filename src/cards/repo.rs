@@ -0,0 +1,176 @@
+use svg::{
+    node::{
+        self,
+        element::{Circle, Group, Rectangle, TSpan, Text, SVG},
+    },
+    Document, Node,
+};
+
+use super::{
+    flex_layout, icons::Icon, measure_text, title_width, truncate_text, wrap_text_multiline,
+    CardBuilder, NumberFormatter, TITLE_FONT_SIZE,
+};
+use crate::{config::Theme, github::repo::RepoInfo, utils::encode_html};
+
+const CARD_WIDTH: u16 = 400;
+const DEFAULT_LANG_COLOR: &str = "#858585";
+const DESCRIPTION_LINE_WIDTH: usize = 59;
+const DESCRIPTION_MAX_LINES: usize = 3;
+const LINE_HEIGHT: u16 = 10;
+/// `.gray`
+const FOOTER_FONT_SIZE: f32 = 12.;
+const BADGE_WIDTH: u16 = 70;
+
+fn create_description_node(description: &str) -> Text {
+    let lines = wrap_text_multiline(description, DESCRIPTION_LINE_WIDTH, DESCRIPTION_MAX_LINES);
+    let mut text = Text::new()
+        .set("class", "description")
+        .set("x", 25)
+        .set("y", -5);
+    for line in lines {
+        text.append(
+            TSpan::new()
+                .set("dy", "1.2em")
+                .set("x", 25)
+                .add(node::Text::new(encode_html(&line))),
+        );
+    }
+    text
+}
+
+fn create_language_node(name: &str, color: &str) -> Group {
+    let circle = Circle::new()
+        .set("data-testid", "primary-lang-color")
+        .set("cx", 0)
+        .set("cy", -5)
+        .set("r", 6)
+        .set("fill", color);
+    let text = Text::new()
+        .set("data-testid", "primary-lang")
+        .set("class", "gray")
+        .set("x", 15)
+        .add(node::Text::new(encode_html(name)));
+    Group::new()
+        .set("data-testid", "primary-lang")
+        .add(circle)
+        .add(text)
+}
+
+fn create_icon_with_label(icon: Icon, label: &str) -> Group {
+    let icon_svg = SVG::new()
+        .set("class", "icon")
+        .set("y", -12)
+        .set("viewBox", (0, 0, 16, 16))
+        .set("version", "1.1")
+        .set("width", 16)
+        .set("height", 16)
+        .add(icon.svg_path());
+    let text = Text::new()
+        .set("data-testid", icon.as_str())
+        .set("class", "gray")
+        .set("x", 25)
+        .add(node::Text::new(label));
+    Group::new().add(icon_svg).add(text)
+}
+
+fn create_badge_node(label: &str, theme: &Theme) -> Group {
+    let rect = Rectangle::new()
+        .set("data-testid", "badge")
+        .set("rx", 15)
+        .set("ry", 15)
+        .set("x", 0)
+        .set("y", 0)
+        .set("width", BADGE_WIDTH)
+        .set("height", 20)
+        .set("fill", theme.text.as_ref())
+        .set("fill-opacity", 0.2);
+    let text = Text::new()
+        .set("x", 35)
+        .set("y", 10)
+        .set("alignment-baseline", "central")
+        .set("dominant-baseline", "central")
+        .set("text-anchor", "middle")
+        .set("fill", theme.text.as_ref())
+        .add(node::Text::new(label));
+    Group::new()
+        .set("class", "badge")
+        .set(
+            "transform",
+            format!("translate({}, -47)", CARD_WIDTH - 25 - BADGE_WIDTH),
+        )
+        .add(rect)
+        .add(text)
+}
+
+/// width of a footer item, `label_x` is where the label follows the icon or dot
+fn footer_item_width(label_x: u16, label: &str) -> u16 {
+    label_x + measure_text(label, FOOTER_FONT_SIZE, false).ceil() as u16
+}
+
+pub fn form_repo_card(repo: RepoInfo, numbers: NumberFormatter, theme: Theme) -> Document {
+    let description = repo
+        .description
+        .clone()
+        .filter(|i| !i.trim().is_empty())
+        .unwrap_or("No description provided".to_string());
+    let description_lines =
+        wrap_text_multiline(&description, DESCRIPTION_LINE_WIDTH, DESCRIPTION_MAX_LINES).len()
+            as u16;
+    let height = if description_lines > 1 { 120 } else { 110 } + description_lines * LINE_HEIGHT;
+
+    // languages, stars and forks in one line
+    let mut footer = Group::new().set("transform", format!("translate(30, {})", height - 75));
+    let mut items = vec![];
+    let mut sizes = vec![];
+    if let Some(language) = &repo.language {
+        let color = repo.language_color.as_deref().unwrap_or(DEFAULT_LANG_COLOR);
+        items.push(create_language_node(language, color));
+        sizes.push(footer_item_width(15, language));
+    }
+    for (icon, count) in [(Icon::Star, repo.stars), (Icon::Fork, repo.forks)] {
+        let count = numbers.format(count);
+        items.push(create_icon_with_label(icon, &count));
+        sizes.push(footer_item_width(25, &count));
+    }
+    for item in flex_layout(items, &sizes, 20, "") {
+        footer.append(item);
+    }
+
+    let badge = repo.is_archived || repo.is_template;
+    // the badge takes the right end of the title line
+    let title = if badge {
+        let max_width = CARD_WIDTH - title_width("") - BADGE_WIDTH - 10;
+        truncate_text(&repo.name, max_width as f32, TITLE_FONT_SIZE, true)
+    } else {
+        repo.name.clone()
+    };
+
+    let mut body = Group::new().add(create_description_node(&description));
+    if repo.is_archived {
+        body.append(create_badge_node("Archived", &theme));
+    } else if repo.is_template {
+        body.append(create_badge_node("Template", &theme));
+    }
+    body.append(footer);
+
+    let css = format!(
+        r#"
+      .description {{ font: 400 13px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}
+      .gray {{ font: 400 12px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}
+      .icon {{ fill: {} }}
+      .badge {{ font: 600 11px 'Segoe UI', Ubuntu, sans-serif; }}
+    "#,
+        theme.text, theme.text, theme.icon
+    );
+    CardBuilder::default()
+        .with_width(CARD_WIDTH)
+        .with_height(height)
+        .with_title(title)
+        .with_title_icon(Icon::Icon)
+        .with_theme(theme)
+        .with_css(css)
+        .with_a11y_title(&repo.name_with_owner)
+        .with_a11y_desc(description)
+        .build()
+        .render(body)
+}
@@ -1,3 +1,4 @@
+use serde::Deserialize;
 use svg::{
     node::{
         self,
@@ -6,18 +7,125 @@ use svg::{
     Document, Node,
 };
 
-use super::{flex_layout, icons::*, style::get_styles, CardBuilder};
-use crate::{config::Theme, github::stats::UserGithubStats, utils::current_year};
+use super::{
+    flex_layout, icons::*, measure_text, style::get_styles, title_width, CardBuilder,
+    NumberFormatter,
+};
+use crate::{
+    config::{RankModel, Theme},
+    github::stats::{Rank, UserGithubStats},
+    utils::{current_year, deserialize_comma_separated},
+};
+
+const DEFAULT_CARD_WIDTH: u16 = 495;
+const MIN_CARD_WIDTH: u16 = 287;
+const MAX_CARD_WIDTH: u16 = 1000;
+const RANK_CARD_MIN_WIDTH: u16 = 420;
+const DEFAULT_LINE_HEIGHT: u16 = 25;
+const MIN_LINE_HEIGHT: u16 = 10;
+const MAX_LINE_HEIGHT: u16 = 100;
+/// a card without `card_width` grows up to this to show a long title
+const MAX_AUTO_WIDTH: u16 = 700;
+/// `.stat`
+const STAT_FONT_SIZE: f32 = 14.;
+/// between the longest label and the values
+const VALUE_GAP: u16 = 15;
+/// room for the rank circle right of the stats
+const RANK_WIDTH: u16 = 155;
+
+/// display options of the stats card, parsed from query parameters
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StatsCardOptions {
+    /// stats to hide: `stars`, `commits`, `prs`, `issues`, `contribs`
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub hide: Vec<String>,
+    pub show_icons: bool,
+    pub hide_rank: bool,
+    /// `classic` or `percentile`, defaults to the configured model
+    pub rank_model: Option<RankModel>,
+    /// show the percentile instead of the level in the rank circle, the classic
+    /// model has none
+    pub show_percentile: bool,
+    pub hide_title: bool,
+    pub hide_border: bool,
+    /// count contributions to private repositories as commits
+    pub include_private: bool,
+    /// repos whose stars are not counted in
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub exclude_repo: Vec<String>,
+    pub card_width: Option<u16>,
+    pub line_height: Option<u16>,
+    pub border_radius: Option<f32>,
+    pub disable_animations: bool,
+    pub custom_title: Option<String>,
+}
+
+impl Default for StatsCardOptions {
+    fn default() -> Self {
+        Self {
+            hide: vec![],
+            show_icons: true,
+            hide_rank: false,
+            rank_model: None,
+            show_percentile: false,
+            hide_title: false,
+            hide_border: false,
+            include_private: false,
+            exclude_repo: vec![],
+            card_width: None,
+            line_height: None,
+            border_radius: None,
+            disable_animations: false,
+            custom_title: None,
+        }
+    }
+}
+
+impl StatsCardOptions {
+    /// check values out of range, return a message for user if any
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(width) = self.card_width {
+            if !(MIN_CARD_WIDTH..=MAX_CARD_WIDTH).contains(&width) {
+                return Err(format!(
+                    "card_width must be between {MIN_CARD_WIDTH} and {MAX_CARD_WIDTH}"
+                ));
+            }
+        }
+        if let Some(line_height) = self.line_height {
+            if !(MIN_LINE_HEIGHT..=MAX_LINE_HEIGHT).contains(&line_height) {
+                return Err(format!(
+                    "line_height must be between {MIN_LINE_HEIGHT} and {MAX_LINE_HEIGHT}"
+                ));
+            }
+        }
+        if let Some(radius) = self.border_radius {
+            if !radius.is_finite() || radius < 0. {
+                return Err("border_radius must be a non-negative number".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// name of a stat in `hide`, `stars` like github-readme-stats while the
+/// icon id is `star`
+fn hide_name(icon: Icon) -> &'static str {
+    match icon {
+        Icon::Star => "stars",
+        _ => icon.as_str(),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct StatItem {
     icon: Icon,
     label: String,
-    value: i64,
+    value: String,
 }
 
 impl StatItem {
-    pub fn new<T: Into<String>>(icon: Icon, label: T, value: i64) -> Self {
+    pub fn new<T: Into<String>>(icon: Icon, label: T, value: String) -> Self {
         Self {
             icon,
             label: label.into(),
@@ -25,7 +133,30 @@ impl StatItem {
         }
     }
 
-    pub fn create_text_node(&self, index: usize, show_icons: bool, bold: bool) -> Group {
+    /// x of the label, after the icon if shown
+    fn label_x(show_icons: bool) -> u16 {
+        if show_icons {
+            25
+        } else {
+            0
+        }
+    }
+
+    fn label_width(&self, bold: bool) -> u16 {
+        measure_text(&self.label, STAT_FONT_SIZE, bold).ceil() as u16
+    }
+
+    fn value_width(&self, bold: bool) -> u16 {
+        measure_text(&self.value, STAT_FONT_SIZE, bold).ceil() as u16
+    }
+
+    pub fn create_text_node(
+        &self,
+        index: usize,
+        show_icons: bool,
+        bold: bool,
+        value_x: u16,
+    ) -> Group {
         let stagger_delay = (index + 3) * 150;
 
         let icon_svg = SVG::new()
@@ -52,34 +183,71 @@ impl StatItem {
             .set("y", 12.5)
             .add(node::Text::new(&self.label));
         if show_icons {
-            text = text.set("x", 25);
+            text = text.set("x", Self::label_x(show_icons));
         }
 
-        let text_x = if show_icons { 140 + 79 } else { 120 + 79 };
         let text_2 = Text::new()
-            .set("x", text_x)
+            .set("x", value_x)
             .set("y", 12.5)
             .set("class", text_class)
             .set("data-testid", self.icon.as_str())
-            .add(node::Text::new(format!("{}", self.value)));
+            .add(node::Text::new(&self.value));
         g.add(text).add(text_2)
     }
 }
 
 pub fn form_stats_card(
     github: UserGithubStats,
-    hide_rank: bool,
-    show_icons: bool,
+    rank: Rank,
+    options: &StatsCardOptions,
+    numbers: NumberFormatter,
     theme: Theme,
 ) -> Document {
-    let line_height = 25;
-    let width = 495;
+    let hide_rank = options.hide_rank;
+    let show_icons = options.show_icons;
+    let line_height = options.line_height.unwrap_or(DEFAULT_LINE_HEIGHT);
+    let min_width = if hide_rank {
+        MIN_CARD_WIDTH
+    } else {
+        RANK_CARD_MIN_WIDTH
+    } + if show_icons { 17 } else { 0 };
+    let stat_collections = get_stat_collections(&github, &options.hide, numbers);
 
-    let stat_collections = get_stat_collections(&github);
-    let height = std::cmp::max(
+    // values follow the longest label, the card grows to fit them
+    let bold = true;
+    let value_x = stat_collections
+        .iter()
+        .map(|item| StatItem::label_x(show_icons) + item.label_width(bold) + VALUE_GAP)
+        .max()
+        .unwrap_or_default()
+        .max(if show_icons { 140 + 79 } else { 120 + 79 });
+    let values_width = stat_collections
+        .iter()
+        .map(|item| item.value_width(bold))
+        .max()
+        .unwrap_or_default();
+    let min_width =
+        min_width.max(25 + value_x + values_width + if hide_rank { 25 } else { RANK_WIDTH });
+    let title = options
+        .custom_title
+        .clone()
+        .unwrap_or(format!("{}'s GitHub Stats", &github.name));
+    let width = match options.card_width {
+        Some(width) => width,
+        // long titles are cut with an ellipsis past the max
+        None if !options.hide_title => {
+            DEFAULT_CARD_WIDTH.max(title_width(&title).min(MAX_AUTO_WIDTH))
+        }
+        None => DEFAULT_CARD_WIDTH,
+    }
+    .max(min_width);
+    let mut height = std::cmp::max(
         45 + (stat_collections.len() as u16 + 1) * line_height,
         if hide_rank { 0 } else { 150 },
     );
+    if options.hide_title {
+        height -= 30;
+    }
 
     let rank_circle = if hide_rank {
         Group::new()
@@ -100,7 +268,12 @@ pub fn form_stats_card(
             .set("alignment-baseline", "central")
             .set("dominant-baseline", "central")
             .set("text-anchor", "middle")
-            .add(node::Text::new(&github.rank.level));
+            .add(node::Text::new(
+                match rank.percentile.filter(|_| options.show_percentile) {
+                    Some(percentile) => numbers.percent(percentile, 1),
+                    None => rank.level.clone(),
+                },
+            ));
         let g_rank_text = Group::new().set("class", "rank-text").add(rank_text);
 
         let rank_x_translation = {
@@ -133,43 +306,67 @@ pub fn form_stats_card(
     let stat_items_inner = stat_collections
         .into_iter()
         .enumerate()
-        .map(|(idx, item)| item.create_text_node(idx, show_icons, true))
+        .map(|(idx, item)| item.create_text_node(idx, show_icons, bold, value_x))
         .collect();
-    for item in flex_layout(stat_items_inner, line_height, "column") {
+    for item in flex_layout(stat_items_inner, &[], line_height, "column") {
         stat_items.append(item);
     }
 
     let body = Group::new().add(rank_circle).add(stat_items);
 
-    let css = get_styles(&theme, show_icons, (100 - &github.rank.score).into());
-    CardBuilder::default()
+    let css = get_styles(&theme, show_icons, (100. - rank.score) as f32);
+    let mut builder = CardBuilder::default()
         .with_width(width)
         .with_height(height)
-        .with_title(format!("{}'s GitHub Stats", &github.name))
+        .with_title(title)
+        .with_hide_title(options.hide_title)
+        .with_hide_border(options.hide_border)
+        .with_animations(!options.disable_animations)
         .with_css(css)
-        .with_a11y_title(format!(
-            "{}'s GitHub Stats, Rank: {}",
-            &github.name, &github.rank.level
-        ))
+        .with_a11y_title(match rank.percentile {
+            Some(percentile) => format!(
+                "{}'s GitHub Stats, Rank: {} (top {})",
+                &github.name,
+                &rank.level,
+                numbers.percent(percentile, 1)
+            ),
+            None => format!("{}'s GitHub Stats, Rank: {}", &github.name, &rank.level),
+        })
         .with_a11y_desc(a11y_desc)
-        .with_theme(theme)
-        .build()
-        .render(body)
+        .with_theme(theme);
+    if let Some(border_radius) = options.border_radius {
+        builder = builder.with_border_radius(border_radius);
+    }
+    builder.build().render(body)
 }
 
-fn get_stat_collections(github: &UserGithubStats) -> Vec<StatItem> {
+fn get_stat_collections(
+    github: &UserGithubStats,
+    hide: &[String],
+    numbers: NumberFormatter,
+) -> Vec<StatItem> {
     let mut result = vec![];
     for icon in Icon::all() {
+        if hide
+            .iter()
+            .any(|i| i == hide_name(icon) || i == icon.as_str())
+        {
+            continue;
+        }
         let item = match icon {
-            Icon::Star => StatItem::new(icon, "Total Stars Earned: ", github.stars),
+            Icon::Star => StatItem::new(icon, "Total Stars Earned: ", numbers.format(github.stars)),
             Icon::Commits => StatItem::new(
                 icon,
                 format!("Total Commits ({}): ", current_year()),
-                github.commits,
+                numbers.format(github.commits),
+            ),
+            Icon::Prs => StatItem::new(icon, "Total PRs: ", numbers.format(github.prs)),
+            Icon::Issues => StatItem::new(icon, "Total Issues: ", numbers.format(github.issues)),
+            Icon::Contribs => StatItem::new(
+                icon,
+                "Contributed to (last year): ",
+                numbers.format(github.contribs),
             ),
-            Icon::Prs => StatItem::new(icon, "Total PRs: ", github.prs),
-            Icon::Issues => StatItem::new(icon, "Total Issues: ", github.issues),
-            Icon::Contribs => StatItem::new(icon, "Contributed to (last year): ", github.contribs),
             _ => continue,
         };
         result.push(item)
@@ -177,3 +374,84 @@ fn get_stat_collections(github: &UserGithubStats) -> Vec<StatItem> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use resvg::usvg;
+
+    use super::*;
+    use crate::config::RankConfig;
+
+    #[test]
+    fn test_hide_stats() {
+        let github = UserGithubStats::default();
+        let numbers = NumberFormatter::default();
+        for (name, icon) in [
+            ("stars", Icon::Star),
+            ("commits", Icon::Commits),
+            ("prs", Icon::Prs),
+            ("issues", Icon::Issues),
+            ("contribs", Icon::Contribs),
+        ] {
+            let items = get_stat_collections(&github, &[name.to_string()], numbers);
+            assert_eq!(items.len(), 4, "{name}");
+            assert!(items.iter().all(|i| i.icon != icon), "{name}");
+        }
+        // the icon id still works
+        let items = get_stat_collections(&github, &["star".to_string()], numbers);
+        assert!(items.iter().all(|i| i.icon != Icon::Star));
+    }
+
+    #[test]
+    fn test_escape_name() {
+        let github = UserGithubStats {
+            name: "a<b&c".to_string(),
+            ..Default::default()
+        };
+        for model in [RankModel::Classic, RankModel::Percentile] {
+            let rank = github.calculate_rank(&RankConfig::default(), model);
+            let svg = form_stats_card(
+                github.clone(),
+                rank,
+                &StatsCardOptions::default(),
+                NumberFormatter::default(),
+                Theme::default(),
+            )
+            .to_string();
+            let options = usvg::Options::default();
+            assert!(
+                usvg::Tree::from_str(&svg, &options, &usvg::fontdb::Database::new()).is_ok(),
+                "{svg}"
+            );
+            assert!(
+                svg.contains("a&lt;b&amp;c&#39;s GitHub Stats, Rank: "),
+                "{svg}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(StatsCardOptions::default().validate().is_ok());
+        for options in [
+            StatsCardOptions {
+                card_width: Some(MAX_CARD_WIDTH + 1),
+                ..Default::default()
+            },
+            StatsCardOptions {
+                card_width: Some(100),
+                ..Default::default()
+            },
+            StatsCardOptions {
+                line_height: Some(11000),
+                ..Default::default()
+            },
+            StatsCardOptions {
+                border_radius: Some(f32::NAN),
+                ..Default::default()
+            },
+        ] {
+            assert!(options.validate().is_err(), "{options:?}");
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use chrono::NaiveDate;
+use svg::{
+    node::{
+        self,
+        element::{Circle, Group, Line, Text},
+    },
+    Document,
+};
+
+use super::{icons::Icon, CardBuilder, NumberFormatter};
+use crate::{config::Theme, github::streak::UserStreak, utils::today};
+
+const CARD_WIDTH: u16 = 495;
+const CARD_HEIGHT: u16 = 200;
+const COLUMN_WIDTH: u16 = CARD_WIDTH / 3;
+
+/// `2023-01-05` to `Jan 5, 2023`, `Present` for today
+fn format_date(date: &str) -> String {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(d) if d == today() => "Present".to_string(),
+        Ok(d) => d.format("%b %-d, %Y").to_string(),
+        Err(_) => date.to_string(),
+    }
+}
+
+fn format_range(start: &str, end: &str) -> String {
+    if start.is_empty() {
+        return format_date(&today().format("%Y-%m-%d").to_string());
+    }
+    if start == end {
+        return format_date(start);
+    }
+    format!("{} - {}", format_date(start), format_date(end))
+}
+
+fn create_text(x: u16, y: u16, class: &str, content: String) -> Text {
+    Text::new()
+        .set("x", x)
+        .set("y", y)
+        .set("class", class)
+        .set("text-anchor", "middle")
+        .set("dominant-baseline", "central")
+        .add(node::Text::new(content))
+}
+
+fn create_column(index: u16, value: String, label: &str, range: String, ring: bool) -> Group {
+    let center = COLUMN_WIDTH * index + COLUMN_WIDTH / 2;
+    let mut g = Group::new()
+        .set("class", "stagger")
+        .set("style", format!("animation-delay: {}ms", (index + 3) * 150));
+    if ring {
+        g = g.add(
+            Circle::new()
+                .set("class", "streak-ring")
+                .set("cx", center)
+                .set("cy", 35)
+                .set("r", 36),
+        );
+    }
+    g.add(create_text(
+        center,
+        35,
+        if ring {
+            "stat-value current"
+        } else {
+            "stat-value"
+        },
+        value,
+    ))
+    .add(create_text(
+        center,
+        95,
+        if ring {
+            "stat-label current"
+        } else {
+            "stat-label"
+        },
+        label.to_string(),
+    ))
+    .add(create_text(center, 118, "stat-range", range))
+}
+
+fn create_separator(index: u16) -> Line {
+    Line::new()
+        .set("class", "separator")
+        .set("x1", COLUMN_WIDTH * index)
+        .set("y1", 0)
+        .set("x2", COLUMN_WIDTH * index)
+        .set("y2", 125)
+}
+
+pub fn form_streak_card(streak: UserStreak, numbers: NumberFormatter, theme: Theme) -> Document {
+    let total_range = format_range(
+        &streak.first_contribution,
+        &today().format("%Y-%m-%d").to_string(),
+    );
+    let body = Group::new()
+        .add(create_column(
+            0,
+            numbers.format(streak.total_contributions),
+            "Total Contributions",
+            total_range,
+            false,
+        ))
+        .add(create_separator(1))
+        .add(create_column(
+            1,
+            numbers.format(streak.current.length.into()),
+            "Current Streak",
+            format_range(&streak.current.start, &streak.current.end),
+            true,
+        ))
+        .add(create_separator(2))
+        .add(create_column(
+            2,
+            numbers.format(streak.longest.length.into()),
+            "Longest Streak",
+            format_range(&streak.longest.start, &streak.longest.end),
+            false,
+        ));
+
+    let ring = theme.ring.as_ref().unwrap_or(&theme.title);
+    let css = format!(
+        r#"
+      .stat-value {{ font: 700 28px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}
+      .stat-label {{ font: 400 14px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}
+      .stat-range {{ font: 400 12px 'Segoe UI', Ubuntu, sans-serif; fill: {}; opacity: 0.8 }}
+      .current {{ fill: {}; font-weight: 700 }}
+      .streak-ring {{ fill: none; stroke: {}; stroke-width: 5 }}
+      .separator {{ stroke: {}; stroke-width: 1; opacity: 0.4 }}
+      .icon {{ fill: {} }}
+      .stagger {{
+        opacity: 0;
+        animation: fadeInAnimation 0.3s ease-in-out forwards;
+      }}
+    "#,
+        theme.text, theme.text, theme.text, ring, ring, theme.text, theme.icon
+    );
+    let a11y_desc = format!(
+        "Total Contributions: {}, Current Streak: {}, Longest Streak: {}",
+        streak.total_contributions, streak.current.length, streak.longest.length
+    );
+    let title = format!("{}'s Contribution Streak", &streak.name);
+    CardBuilder::default()
+        .with_width(CARD_WIDTH)
+        .with_height(CARD_HEIGHT)
+        .with_title(&title)
+        .with_title_icon(Icon::Commits)
+        .with_theme(theme)
+        .with_css(css)
+        .with_a11y_title(title)
+        .with_a11y_desc(a11y_desc)
+        .build()
+        .render(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use resvg::usvg;
+
+    use super::*;
+
+    #[test]
+    fn test_escape_name() {
+        let streak = UserStreak {
+            name: "a<b&c".to_string(),
+            ..Default::default()
+        };
+        let svg =
+            form_streak_card(streak, NumberFormatter::default(), Theme::default()).to_string();
+        let options = usvg::Options::default();
+        assert!(
+            usvg::Tree::from_str(&svg, &options, &usvg::fontdb::Database::new()).is_ok(),
+            "{svg}"
+        );
+        assert!(
+            svg.contains("a&lt;b&amp;c&#39;s Contribution Streak"),
+            "{svg}"
+        );
+    }
+}
@@ -27,7 +27,7 @@ pub fn get_styles(theme: &Theme, show_icons: bool, progress: f32) -> String {
     format!(
         r#"
       .stat {{
-        font: 600 14px 'Segoe UI', Ubuntu, "Helvetica Neue", Sans-Serif; fill: {};
+        font: 600 14px 'Segoe UI', Ubuntu, "Helvetica Neue", sans-serif; fill: {};
       }}
       @supports(-moz-appearance: auto) {{
         /* Selector detects Firefox */
@@ -38,7 +38,7 @@ pub fn get_styles(theme: &Theme, show_icons: bool, progress: f32) -> String {
         animation: fadeInAnimation 0.3s ease-in-out forwards;
       }}
       .rank-text {{
-        font: 800 24px 'Segoe UI', Ubuntu, Sans-Serif; fill: {};
+        font: 800 24px 'Segoe UI', Ubuntu, sans-serif; fill: {};
         animation: scaleInAnimation 0.3s ease-in-out forwards;
       }}
 
@@ -57,6 +57,7 @@ pub fn get_styles(theme: &Theme, show_icons: bool, progress: f32) -> String {
       .rank-circle {{
         stroke: {};
         stroke-dasharray: 250;
+        stroke-dashoffset: {};
         fill: none;
         stroke-width: 6;
         stroke-linecap: round;
@@ -73,6 +74,8 @@ pub fn get_styles(theme: &Theme, show_icons: bool, progress: f32) -> String {
         if show_icons { "block" } else { "none" },
         theme.ring.as_ref().unwrap_or(&theme.title),
         theme.ring.as_ref().unwrap_or(&theme.title),
+        // the final state, for renderers without animations
+        calculate_circle_progress(progress),
         get_progress_animation(progress),
     )
 }
@@ -110,3 +113,40 @@ fn calculate_circle_progress(value: f32) -> f32 {
 
     ((100. - new_value) / 100.) * c
 }
+
+/// parse `#rgb`, `#rrggbb` or `#rrggbbaa` into rgba
+fn parse_hex_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.trim().strip_prefix('#')?;
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>() + "ff",
+        6 => format!("{hex}ff"),
+        8 => hex.to_string(),
+        _ => return None,
+    };
+    let mut rgba = [0; 4];
+    for (i, item) in rgba.iter_mut().enumerate() {
+        *item = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(rgba)
+}
+
+/// linear interpolation between two hex colors, `ratio` 0 is `from` and 1 is `to`
+pub fn mix_colors(from: &str, to: &str, ratio: f32) -> String {
+    let (Some(from), Some(to)) = (parse_hex_color(from), parse_hex_color(to)) else {
+        return to.to_string();
+    };
+    let ratio = ratio.clamp(0., 1.);
+    let mixed: Vec<u8> = from
+        .iter()
+        .zip(to.iter())
+        .map(|(a, b)| (*a as f32 + (*b as f32 - *a as f32) * ratio).round() as u8)
+        .collect();
+    if mixed[3] == 255 {
+        format!("#{:02x}{:02x}{:02x}", mixed[0], mixed[1], mixed[2])
+    } else {
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            mixed[0], mixed[1], mixed[2], mixed[3]
+        )
+    }
+}
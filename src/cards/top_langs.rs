@@ -1,23 +1,117 @@
+use std::f32::consts::PI;
+
+use serde::Deserialize;
 use svg::{
     node,
-    node::element::{Circle, Group, Text, SVG},
+    node::element::{Circle, Definitions, Group, Mask, Path, Rectangle, Text, SVG},
     Document, Node,
 };
 use tracing::trace;
 
-use super::CardBuilder;
+use super::{flex_layout, measure_text, truncate_text, CardBuilder, NumberFormatter};
 use crate::{
     config::Theme,
     github::top_langs::{Lang, TopLangs},
+    utils::deserialize_comma_separated,
 };
 
 const DEFAULT_CARD_WIDTH: u16 = 300;
 const MIN_CARD_WIDTH: u16 = 230;
+const MAX_CARD_WIDTH: u16 = 1000;
 const DEFAULT_LANGS_COUNT: u8 = 5;
+const MAX_LANGS_COUNT: u8 = 10;
+/// larger weights overflow the score of big repos
+const MAX_WEIGHT: f64 = 4.;
 const DEFAULT_LANG_COLOR: &str = "#858585";
 const CARD_PADDING: usize = 25;
+const DONUT_STROKE_WIDTH: f32 = 12.;
+/// `.lang-name`
+const LANG_NAME_FONT_SIZE: f32 = 11.;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// one progress bar per language
+    #[default]
+    Normal,
+    /// one stacked progress bar with a two columns legend
+    Compact,
+    /// donut chart beside the legend
+    Donut,
+    /// donut chart above the legend
+    DonutVertical,
+    /// pie chart above the legend
+    Pie,
+}
+
+/// display options of the top languages card, parsed from query parameters
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TopLangsCardOptions {
+    /// languages to hide, case insensitive
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub hide: Vec<String>,
+    pub layout: Layout,
+    pub card_width: Option<u16>,
+    pub langs_count: Option<u8>,
+    /// repos not counted in
+    #[serde(deserialize_with = "deserialize_comma_separated")]
+    pub exclude_repo: Vec<String>,
+    /// rank languages by `size ^ size_weight * count ^ count_weight`, weights 0 to 4
+    pub size_weight: f64,
+    pub count_weight: f64,
+}
+
+impl Default for TopLangsCardOptions {
+    fn default() -> Self {
+        Self {
+            hide: vec![],
+            layout: Layout::default(),
+            card_width: None,
+            langs_count: None,
+            exclude_repo: vec![],
+            size_weight: 1.,
+            count_weight: 0.,
+        }
+    }
+}
 
-fn create_progress_text_node(width: u16, name: &str, color: &str, progress: f32) -> Group {
+impl TopLangsCardOptions {
+    /// check values out of range, return a message for user if any
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(width) = self.card_width {
+            if !(MIN_CARD_WIDTH..=MAX_CARD_WIDTH).contains(&width) {
+                return Err(format!(
+                    "card_width must be between {MIN_CARD_WIDTH} and {MAX_CARD_WIDTH}"
+                ));
+            }
+        }
+        if let Some(count) = self.langs_count {
+            if !(1..=MAX_LANGS_COUNT).contains(&count) {
+                return Err(format!(
+                    "langs_count must be between 1 and {MAX_LANGS_COUNT}"
+                ));
+            }
+        }
+        for (name, weight) in [
+            ("size_weight", self.size_weight),
+            ("count_weight", self.count_weight),
+        ] {
+            if !(0. ..=MAX_WEIGHT).contains(&weight) {
+                return Err(format!("{name} must be between 0 and {MAX_WEIGHT}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn create_progress_text_node(
+    width: u16,
+    name: &str,
+    color: &str,
+    progress: f32,
+    numbers: NumberFormatter,
+) -> Group {
     let padding_right = 95;
     let progress_text_x = width - padding_right + 10;
     let progress_width = width - padding_right;
@@ -32,7 +126,7 @@ fn create_progress_text_node(width: u16, name: &str, color: &str, progress: f32)
         .set("x", progress_text_x)
         .set("y", "34")
         .set("class", "lang-name")
-        .add(node::Text::new(format!("{progress:.2}%")));
+        .add(node::Text::new(numbers.percent(progress as f64, 2)));
     let progress_node =
         super::progress::create_progress_node(0, 25, progress_width, color, progress, "#ddd");
 
@@ -42,10 +136,18 @@ fn create_progress_text_node(width: u16, name: &str, color: &str, progress: f32)
         .add(progress_node)
 }
 
-#[allow(dead_code)]
-fn create_compact_lang_node(lang: Lang, total_size: usize) -> Group {
-    let percentage = lang.size * 100 / total_size;
-    let color = lang.color.unwrap_or("#858585".to_string());
+/// the name is cut to fit the node in `max_width`
+fn create_compact_lang_node(
+    lang: &Lang,
+    total_size: usize,
+    max_width: f32,
+    numbers: NumberFormatter,
+) -> Group {
+    let percentage = lang.size as f64 * 100. / total_size as f64;
+    let percentage = format!(" {}", numbers.percent(percentage, 2));
+    let name_width = max_width - 15. - measure_text(&percentage, LANG_NAME_FONT_SIZE, false);
+    let name = truncate_text(&lang.name, name_width, LANG_NAME_FONT_SIZE, false);
+    let color = lang.color.as_deref().unwrap_or(DEFAULT_LANG_COLOR);
 
     let circle = Circle::new()
         .set("cx", 5)
@@ -57,38 +159,221 @@ fn create_compact_lang_node(lang: Lang, total_size: usize) -> Group {
         .set("x", 15)
         .set("y", 10)
         .set("class", "lang-name")
-        .add(node::Text::new(format!("{} {}%", lang.name, percentage)));
+        .add(node::Text::new(format!("{name}{percentage}")));
     Group::new().add(circle).add(lang_text)
 }
 
-fn render_normal_layout(langs: Vec<Lang>, width: u16) -> Vec<Group> {
+/// languages legend in two columns
+fn create_lang_legend(
+    langs: &[Lang],
+    total_size: usize,
+    width: u16,
+    numbers: NumberFormatter,
+) -> Group {
+    let (left, right) = langs.split_at(langs.len().div_ceil(2));
+    let column_width = (width / 2 - 10) as f32;
+    let mut left_column = Group::new();
+    for item in flex_layout(
+        left.iter()
+            .map(|lang| create_compact_lang_node(lang, total_size, column_width, numbers))
+            .collect(),
+        &[],
+        25,
+        "column",
+    ) {
+        left_column.append(item);
+    }
+    let mut right_column = Group::new().set("transform", format!("translate({}, 0)", width / 2));
+    for item in flex_layout(
+        right
+            .iter()
+            .map(|lang| create_compact_lang_node(lang, total_size, column_width, numbers))
+            .collect(),
+        &[],
+        25,
+        "column",
+    ) {
+        right_column.append(item);
+    }
+
+    Group::new().add(left_column).add(right_column)
+}
+
+fn render_normal_layout(langs: Vec<Lang>, width: u16, numbers: NumberFormatter) -> Vec<Group> {
     let total_language_size: usize = langs.iter().map(|i| i.size).sum();
     let items = langs
         .iter()
         .map(|lang| {
             let color = lang.color.clone().unwrap_or(DEFAULT_LANG_COLOR.to_owned());
             let progress: f32 = lang.size as f32 * 100. / total_language_size as f32;
-            create_progress_text_node(width, &lang.name, &color, progress)
+            create_progress_text_node(width, &lang.name, &color, progress, numbers)
         })
         .collect();
-    super::flex_layout(items, 40, "column")
+    super::flex_layout(items, &[], 40, "column")
 }
 
 fn calculate_normal_layout_height(total_langs: u16) -> u16 {
     45 + (total_langs + 1) * 40
 }
 
-fn use_languages(top_langs: TopLangs, hide: Vec<String>, langs_count: u8) -> Vec<Lang> {
-    let langs_count = langs_count.clamp(1, 10);
-    let langs_to_hide: Vec<String> = hide
-        .into_iter()
+fn render_compact_layout(langs: Vec<Lang>, width: u16, numbers: NumberFormatter) -> Vec<Group> {
+    let total_language_size: usize = langs.iter().map(|i| i.size).sum();
+    let offset_width = width - 2 * CARD_PADDING as u16;
+
+    let mask = Mask::new().set("id", "rect-mask").add(
+        Rectangle::new()
+            .set("x", 0)
+            .set("y", 0)
+            .set("width", offset_width)
+            .set("height", 8)
+            .set("fill", "white")
+            .set("rx", 5),
+    );
+    let mut progress_bar = Group::new().add(Definitions::new().add(mask));
+    let mut progress_offset = 0.;
+    for lang in &langs {
+        let progress = lang.size as f32 * offset_width as f32 / total_language_size as f32;
+        progress_bar.append(
+            Rectangle::new()
+                .set("mask", "url(#rect-mask)")
+                .set("data-testid", "lang-progress")
+                .set("x", progress_offset)
+                .set("y", 0)
+                .set("width", progress)
+                .set("height", 8)
+                .set("fill", lang.color.as_deref().unwrap_or(DEFAULT_LANG_COLOR)),
+        );
+        progress_offset += progress;
+    }
+
+    let legend = create_lang_legend(&langs, total_language_size, offset_width, numbers)
+        .set("transform", "translate(0, 25)");
+    vec![progress_bar, legend]
+}
+
+fn calculate_compact_layout_height(total_langs: u16) -> u16 {
+    90 + total_langs.div_ceil(2) * 25
+}
+
+/// point on the circle, `angle` in degrees clockwise from twelve o'clock
+fn polar_to_cartesian(cx: f32, cy: f32, radius: f32, angle: f32) -> (f32, f32) {
+    let radians = (angle - 90.) * PI / 180.;
+    (cx + radius * radians.cos(), cy + radius * radians.sin())
+}
+
+/// donut (stroked arcs) or pie (filled sectors) chart centered at `(cx, cy)`
+fn create_chart_node(langs: &[Lang], cx: f32, cy: f32, radius: f32, pie: bool) -> Group {
+    let total_language_size: usize = langs.iter().map(|i| i.size).sum();
+    let mut chart = Group::new().set("data-testid", if pie { "lang-pie" } else { "lang-donut" });
+
+    let mut start_angle = 0.;
+    for lang in langs {
+        let color = lang.color.as_deref().unwrap_or(DEFAULT_LANG_COLOR);
+        let sweep = lang.size as f32 * 360. / total_language_size as f32;
+        // an arc can not start and end at the same point, use a circle instead
+        if sweep >= 359.99 {
+            let circle = Circle::new().set("cx", cx).set("cy", cy).set("r", radius);
+            chart.append(if pie {
+                circle.set("fill", color)
+            } else {
+                circle
+                    .set("fill", "none")
+                    .set("stroke", color)
+                    .set("stroke-width", DONUT_STROKE_WIDTH)
+            });
+            break;
+        }
+
+        let end_angle = start_angle + sweep;
+        let (start_x, start_y) = polar_to_cartesian(cx, cy, radius, start_angle);
+        let (end_x, end_y) = polar_to_cartesian(cx, cy, radius, end_angle);
+        let large_arc = if sweep > 180. { 1 } else { 0 };
+        let arc =
+            format!("M {start_x} {start_y} A {radius} {radius} 0 {large_arc} 1 {end_x} {end_y}");
+        let path = Path::new().set("data-testid", "lang-progress");
+        chart.append(if pie {
+            path.set("d", format!("{arc} L {cx} {cy} Z"))
+                .set("fill", color)
+        } else {
+            path.set("d", arc)
+                .set("fill", "none")
+                .set("stroke", color)
+                .set("stroke-width", DONUT_STROKE_WIDTH)
+        });
+        start_angle = end_angle;
+    }
+    chart
+}
+
+fn render_donut_layout(langs: Vec<Lang>, width: u16, numbers: NumberFormatter) -> Vec<Group> {
+    let total_language_size: usize = langs.iter().map(|i| i.size).sum();
+    let offset_width = (width - 2 * CARD_PADDING as u16) as f32;
+    let radius = 50.;
+    let legend_width = offset_width - 2. * radius - 20.;
+
+    let mut legend = Group::new();
+    for item in flex_layout(
+        langs
+            .iter()
+            .map(|lang| create_compact_lang_node(lang, total_language_size, legend_width, numbers))
+            .collect(),
+        &[],
+        32,
+        "column",
+    ) {
+        legend.append(item);
+    }
+    let donut = create_chart_node(&langs, offset_width - radius - 10., 60., radius, false);
+    vec![legend, Group::new().add(donut)]
+}
+
+fn calculate_donut_layout_height(total_langs: u16) -> u16 {
+    215 + total_langs.saturating_sub(5) * 32
+}
+
+fn render_vertical_layout(
+    langs: Vec<Lang>,
+    width: u16,
+    pie: bool,
+    numbers: NumberFormatter,
+) -> Vec<Group> {
+    let total_language_size: usize = langs.iter().map(|i| i.size).sum();
+    let offset_width = width - 2 * CARD_PADDING as u16;
+    let radius = if pie { 70. } else { 64. };
+
+    let chart = create_chart_node(&langs, offset_width as f32 / 2., 75., radius, pie);
+    let legend = create_lang_legend(&langs, total_language_size, offset_width, numbers)
+        .set("transform", "translate(0, 160)");
+    vec![Group::new().add(chart), legend]
+}
+
+fn calculate_vertical_layout_height(total_langs: u16) -> u16 {
+    225 + total_langs.div_ceil(2) * 25
+}
+
+/// languages shown on the card, heaviest first by `size_weight` and `count_weight`,
+/// their sizes stay in bytes
+pub fn use_languages(top_langs: &TopLangs, options: &TopLangsCardOptions) -> Vec<Lang> {
+    let langs_count = options.langs_count.unwrap_or(DEFAULT_LANGS_COUNT);
+    let langs_to_hide: Vec<String> = options
+        .hide
+        .iter()
         .map(|i| i.trim().to_ascii_lowercase())
         .collect();
 
-    let mut result: Vec<Lang> = top_langs.langs.into_values().collect();
-    result.sort_by(|a, b| b.size.cmp(&a.size));
+    let mut result: Vec<(f64, Lang)> = top_langs
+        .langs(&options.exclude_repo)
+        .into_values()
+        .map(|lang| {
+            let score = (lang.size as f64).powf(options.size_weight)
+                * (lang.count as f64).powf(options.count_weight);
+            (score, lang)
+        })
+        .collect();
+    result.sort_by(|(a, _), (b, _)| b.total_cmp(a));
     result
         .into_iter()
+        .map(|(_, lang)| lang)
         .filter(|lang| !langs_to_hide.contains(&lang.name.trim().to_ascii_lowercase()))
         .take(langs_count as usize)
         .collect()
@@ -96,21 +381,36 @@ fn use_languages(top_langs: TopLangs, hide: Vec<String>, langs_count: u8) -> Vec
 
 pub fn form_top_langs_card(
     top_langs: TopLangs,
-    hide: Vec<String>,
-    card_width: Option<u16>,
-    langs_count: Option<u8>,
+    options: &TopLangsCardOptions,
+    numbers: NumberFormatter,
     theme: Theme,
 ) -> Document {
-    let langs = use_languages(top_langs, hide, langs_count.unwrap_or(DEFAULT_LANGS_COUNT));
+    let langs = use_languages(&top_langs, options);
     trace!("{:?}", langs);
-    // DEFAUT
-    let width = match card_width {
-        None => DEFAULT_CARD_WIDTH,
-        Some(n) if n < MIN_CARD_WIDTH => MIN_CARD_WIDTH,
-        _ => card_width.unwrap(),
+    let width = options.card_width.unwrap_or(DEFAULT_CARD_WIDTH);
+    let total_langs = langs.len() as u16;
+    let (height, final_layout) = match options.layout {
+        Layout::Normal => (
+            calculate_normal_layout_height(total_langs),
+            render_normal_layout(langs, width, numbers),
+        ),
+        Layout::Compact => (
+            calculate_compact_layout_height(total_langs),
+            render_compact_layout(langs, width, numbers),
+        ),
+        Layout::Donut => (
+            calculate_donut_layout_height(total_langs),
+            render_donut_layout(langs, width, numbers),
+        ),
+        Layout::DonutVertical => (
+            calculate_vertical_layout_height(total_langs),
+            render_vertical_layout(langs, width, false, numbers),
+        ),
+        Layout::Pie => (
+            calculate_vertical_layout_height(total_langs),
+            render_vertical_layout(langs, width, true, numbers),
+        ),
     };
-    let height = calculate_normal_layout_height(langs.len() as u16);
-    let final_layout = render_normal_layout(langs, width);
 
     let mut body = SVG::new()
         .set("data-testid", "lang-items")
@@ -120,7 +420,7 @@ pub fn form_top_langs_card(
     }
 
     let css = format!(
-        ".lang-name {{ font: 400 11px 'Segoe UI', Ubuntu, Sans-Serif; fill: {} }}",
+        ".lang-name {{ font: 400 11px 'Segoe UI', Ubuntu, sans-serif; fill: {} }}",
         theme.text
     );
     let title = "Most Used Languages";
@@ -137,3 +437,122 @@ pub fn form_top_langs_card(
         .build()
         .render(Group::new().add(body))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github::top_langs::RepoLangs;
+
+    fn lang(name: &str, size: usize, count: usize) -> Lang {
+        Lang {
+            name: name.to_string(),
+            color: None,
+            size,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_use_languages_weights() {
+        let top_langs = TopLangs {
+            repos: vec![RepoLangs {
+                name: "a".to_string(),
+                langs: vec![
+                    lang("Rust", 1 << 40, 1),
+                    lang("Go", 1 << 30, 20),
+                    lang("Shell", 10, 1),
+                ],
+            }],
+            ..Default::default()
+        };
+        let options = TopLangsCardOptions {
+            size_weight: MAX_WEIGHT,
+            count_weight: MAX_WEIGHT,
+            ..Default::default()
+        };
+        assert!(options.validate().is_ok());
+        let langs = use_languages(&top_langs, &options);
+        let names: Vec<_> = langs.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Rust", "Go", "Shell"]);
+        // sizes stay in bytes for the percentages
+        assert_eq!(langs[0].size, 1 << 40);
+        let total: usize = langs.iter().map(|i| i.size).sum();
+        assert_eq!(total, (1 << 40) + (1 << 30) + 10);
+
+        let options = TopLangsCardOptions {
+            size_weight: 0.,
+            count_weight: 1.,
+            ..Default::default()
+        };
+        assert_eq!(use_languages(&top_langs, &options)[0].name, "Go");
+
+        for weight in [50., -1., f64::NAN, f64::INFINITY] {
+            let options = TopLangsCardOptions {
+                size_weight: weight,
+                ..Default::default()
+            };
+            assert!(options.validate().is_err(), "{weight}");
+        }
+    }
+
+    #[test]
+    fn test_layouts() {
+        let top_langs = TopLangs {
+            repos: vec![RepoLangs {
+                name: "a".to_string(),
+                langs: vec![lang("Rust", 300, 1), lang("Go", 100, 1)],
+            }],
+            ..Default::default()
+        };
+        for (name, height, chart, progress) in [
+            ("normal", 165, None, r#"width="75""#),
+            ("compact", 115, None, "url(#rect-mask)"),
+            ("donut", 215, Some("lang-donut"), "stroke-width"),
+            ("donut-vertical", 250, Some("lang-donut"), "stroke-width"),
+            ("pie", 250, Some("lang-pie"), " Z"),
+        ] {
+            let options = TopLangsCardOptions {
+                layout: serde_json::from_value(serde_json::json!(name)).unwrap(),
+                ..Default::default()
+            };
+            let svg = form_top_langs_card(
+                top_langs.clone(),
+                &options,
+                NumberFormatter::default(),
+                Theme::default(),
+            )
+            .to_string();
+            assert!(svg.contains(&format!(r#"height="{height}""#)), "{name}");
+            assert!(svg.contains("Rust") && svg.contains("Go"), "{name}");
+            assert!(svg.contains("75.00%") && svg.contains("25.00%"), "{name}");
+            // one bar, arc or sector per language
+            assert_eq!(svg.matches(r#"data-testid="lang-progress""#).count(), 2);
+            assert!(svg.contains(progress), "{name}");
+            for id in ["lang-donut", "lang-pie"] {
+                assert_eq!(svg.contains(id), chart == Some(id), "{name}");
+            }
+        }
+
+        // a single language is a full circle, an arc can not close on itself
+        let top_langs = TopLangs {
+            repos: vec![RepoLangs {
+                name: "a".to_string(),
+                langs: vec![lang("Rust", 300, 1)],
+            }],
+            ..Default::default()
+        };
+        let options = TopLangsCardOptions {
+            layout: Layout::Pie,
+            ..Default::default()
+        };
+        let svg = form_top_langs_card(
+            top_langs,
+            &options,
+            NumberFormatter::default(),
+            Theme::default(),
+        )
+        .to_string();
+        assert!(svg.contains("<circle"), "{svg}");
+        assert!(svg.contains("100.00%"), "{svg}");
+    }
+}
@@ -1,15 +1,25 @@
 //! app config
 
-use std::{fmt, path::Path};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use color_eyre::Result;
-use kdl::KdlDocument;
+use color_eyre::{eyre::eyre, Result};
+use kdl::{KdlDocument, KdlValue};
 use tokio::fs::read_to_string;
 
+mod rank;
 mod themes;
 
+pub use rank::{RankConfig, RankLevel, RankModel, StatWeights};
 pub use themes::{Theme, Themes, DEFAULT};
 
+use crate::humantime::parse_duration;
+
 #[derive(Clone)]
 pub struct Config {
     /// default both(ipv4 and ipv6)
@@ -18,10 +28,19 @@ pub struct Config {
     pub listen_port: u16,
     /// monitor on systemd services
     pub services: Vec<String>,
-    /// use to show github stats
-    pub github_api_token: String,
+    /// use to show github stats, `github_api_token "a" "b"`, requests are spread
+    /// over all tokens by their rate limit
+    pub github_api_tokens: Vec<String>,
     /// allow query github stats user list, allow any if empty
     pub allow_users: Vec<String>,
+    /// `cache { }` section
+    pub cache: CacheConfig,
+    /// `admin { }` section
+    pub admin: AdminConfig,
+    /// `github { }` section
+    pub github: GithubConfig,
+    /// `rank { }` section
+    pub rank: RankConfig,
 }
 
 impl fmt::Debug for Config {
@@ -31,10 +50,308 @@ impl fmt::Debug for Config {
             .field("listen_port", &self.listen_port)
             .field("services", &self.services)
             .field("allow_users", &self.allow_users)
+            .field("cache", &self.cache)
+            .field("admin", &self.admin)
+            .field("github", &self.github)
+            .field("rank", &self.rank)
+            .finish()
+    }
+}
+
+/// data types in the cache, each one can have its own ttl
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheKind {
+    Stats,
+    TopLangs,
+    Pin,
+    Streak,
+    Calendar,
+}
+
+impl CacheKind {
+    const ALL: [CacheKind; 5] = [
+        CacheKind::Stats,
+        CacheKind::TopLangs,
+        CacheKind::Pin,
+        CacheKind::Streak,
+        CacheKind::Calendar,
+    ];
+
+    /// property name in `cache { ttl }`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheKind::Stats => "stats",
+            CacheKind::TopLangs => "top_langs",
+            CacheKind::Pin => "pin",
+            CacheKind::Streak => "streak",
+            CacheKind::Calendar => "calendar",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// data older than this is refreshed, default 1h
+    pub ttl: Duration,
+    /// overrides `ttl` per data type, `ttl "1h" stats="4h" top_langs="1d"`
+    pub kind_ttls: HashMap<CacheKind, Duration>,
+    /// serve expired data for this long after `ttl` while refreshing it in background,
+    /// then evict it, default 1d
+    pub stale_ttl: Duration,
+    /// least recently used entries are evicted beyond this, default 1000
+    pub max_entries: usize,
+    /// how often expired entries are evicted and the snapshot is saved, default 10m
+    pub cleanup_interval: Duration,
+    /// snapshot file to keep the cache across restarts, memory only if not set
+    pub path: Option<PathBuf>,
+    /// bounds of the `cache_seconds` query parameter, `cache_seconds min="30m" max="1d"`
+    pub cache_seconds_min: Duration,
+    pub cache_seconds_max: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(60 * 60),
+            kind_ttls: HashMap::new(),
+            stale_ttl: Duration::from_secs(24 * 60 * 60),
+            max_entries: 1000,
+            cleanup_interval: Duration::from_secs(10 * 60),
+            path: None,
+            cache_seconds_min: Duration::from_secs(30 * 60),
+            cache_seconds_max: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl CacheConfig {
+    fn from_kdl(doc: Option<&KdlDocument>) -> Result<Self> {
+        let mut r = Self::default();
+        let Some(doc) = doc else {
+            return Ok(r);
+        };
+        if let Some(node) = doc.get("ttl") {
+            for entry in node.entries() {
+                let ttl = to_duration(entry.value())?;
+                match entry.name().map(|i| i.value()) {
+                    None => r.ttl = ttl,
+                    Some(name) => {
+                        let kind = CacheKind::ALL
+                            .into_iter()
+                            .find(|i| i.as_str() == name)
+                            .ok_or_else(|| eyre!("unknown cache ttl: {name}"))?;
+                        r.kind_ttls.insert(kind, ttl);
+                    }
+                }
+            }
+        }
+        if let Some(stale_ttl) = get_duration(doc, "stale_ttl")? {
+            r.stale_ttl = stale_ttl;
+        }
+        if let Some(max_entries) = doc.get_arg("max_entries").and_then(|i| i.as_i64()) {
+            r.max_entries = max_entries.max(1) as usize;
+        }
+        if let Some(interval) = get_duration(doc, "cleanup_interval")? {
+            r.cleanup_interval = interval;
+        }
+        r.path = doc
+            .get_arg("path")
+            .and_then(|i| i.as_string())
+            .map(PathBuf::from);
+        if let Some(node) = doc.get("cache_seconds") {
+            if let Some(min) = node.get("min") {
+                r.cache_seconds_min = to_duration(min.value())?;
+            }
+            if let Some(max) = node.get("max") {
+                r.cache_seconds_max = to_duration(max.value())?;
+            }
+            if r.cache_seconds_min > r.cache_seconds_max {
+                return Err(eyre!("cache_seconds min is larger than max"));
+            }
+        }
+        Ok(r)
+    }
+
+    pub fn ttl_of(&self, kind: CacheKind) -> Duration {
+        self.kind_ttls.get(&kind).copied().unwrap_or(self.ttl)
+    }
+
+    /// `Cache-Control` max age of a card, the requested `cache_seconds`
+    /// or the ttl of `kind`, clamped to the configured bounds
+    pub fn max_age_of(&self, kind: CacheKind, cache_seconds: Option<u64>) -> Duration {
+        cache_seconds
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| self.ttl_of(kind))
+            .clamp(self.cache_seconds_min, self.cache_seconds_max)
+    }
+}
+
+/// read a human readable duration like `4h` from the first argument of `name`
+fn get_duration(doc: &KdlDocument, name: &str) -> Result<Option<Duration>> {
+    doc.get(name)
+        .and_then(|i| i.get(0))
+        .map(|i| to_duration(i.value()))
+        .transpose()
+}
+
+/// `"4h"` like strings or seconds as integers
+fn to_duration(value: &KdlValue) -> Result<Duration> {
+    if let Some(secs) = value.as_i64() {
+        return u64::try_from(secs)
+            .map(Duration::from_secs)
+            .map_err(|_| eyre!("invalid duration: {value}"));
+    }
+    value
+        .as_string()
+        .and_then(parse_duration)
+        .ok_or_else(|| eyre!("invalid duration: {value}"))
+}
+
+/// http client of the github api
+#[derive(Debug, Clone)]
+pub struct GithubConfig {
+    /// graphql endpoint, `https://<host>/api/graphql` of github enterprise server,
+    /// default `https://api.github.com/graphql`
+    pub api_url: String,
+    /// of a whole request, default 10s
+    pub timeout: Duration,
+    /// default 5s
+    pub connect_timeout: Duration,
+    /// `http://` or `https://` proxy of github requests
+    pub proxy: Option<String>,
+}
+
+impl Default for GithubConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://api.github.com/graphql".to_string(),
+            timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(5),
+            proxy: None,
+        }
+    }
+}
+
+impl GithubConfig {
+    fn from_kdl(doc: Option<&KdlDocument>) -> Result<Self> {
+        let mut r = Self::default();
+        let Some(doc) = doc else {
+            return Ok(r);
+        };
+        if let Some(api_url) = doc.get_arg("api_url").and_then(|i| i.as_string()) {
+            reqwest::Url::parse(api_url)
+                .map_err(|e| eyre!("invalid github api_url {api_url}: {e}"))?;
+            r.api_url = api_url.to_string();
+        }
+        if let Some(timeout) = get_duration(doc, "timeout")? {
+            r.timeout = timeout;
+        }
+        if let Some(connect_timeout) = get_duration(doc, "connect_timeout")? {
+            r.connect_timeout = connect_timeout;
+        }
+        if let Some(proxy) = doc.get_arg("proxy").and_then(|i| i.as_string()) {
+            reqwest::Proxy::all(proxy).map_err(|e| eyre!("invalid github proxy {proxy}: {e}"))?;
+            r.proxy = Some(proxy.to_string());
+        }
+        Ok(r)
+    }
+}
+
+/// routes guarded by `admin { }` if `routes` is not set
+const ADMIN_ROUTES: [&str; 3] = ["/cache/*", "/status", "/api/v1/status"];
+
+/// card endpoints, `routes` matching any of them are rejected
+const CARD_ROUTES: [&str; 5] = [
+    "/stats",
+    "/stats/top-langs",
+    "/stats/pin",
+    "/stats/streak",
+    "/stats/calendar",
+];
+
+/// credentials of the operational endpoints, card endpoints are always public
+#[derive(Clone)]
+pub struct AdminConfig {
+    /// `Authorization: Bearer <token>`
+    pub token: Option<String>,
+    /// http basic credentials, `basic_auth "user" "password"`
+    pub basic_auth: Option<(String, String)>,
+    /// exact paths, or prefixes ending with `/*`, default `/cache/*`, `/status`
+    /// and `/api/v1/status`, denied to all if no credentials are set
+    pub routes: Vec<String>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            basic_auth: None,
+            routes: ADMIN_ROUTES.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field(
+                "basic_auth",
+                &self.basic_auth.as_ref().map(|(user, _)| (user, "***")),
+            )
+            .field("routes", &self.routes)
             .finish()
     }
 }
 
+impl AdminConfig {
+    fn from_kdl(doc: Option<&KdlDocument>) -> Result<Self> {
+        let mut r = Self::default();
+        let Some(doc) = doc else {
+            return Ok(r);
+        };
+        let non_empty = |value: &KdlValue| {
+            value
+                .as_string()
+                .filter(|i| !i.is_empty())
+                .map(|i| i.to_string())
+                .ok_or_else(|| eyre!("admin values must be non-empty strings: {value}"))
+        };
+        r.token = doc.get_arg("token").map(non_empty).transpose()?;
+        if let Some(node) = doc.get("basic_auth") {
+            let (Some(user), Some(password)) = (node.get(0), node.get(1)) else {
+                return Err(eyre!("basic_auth needs a user and a password"));
+            };
+            r.basic_auth = Some((non_empty(user.value())?, non_empty(password.value())?));
+        }
+        if doc.get("routes").is_some() {
+            r.routes = doc
+                .get_args("routes")
+                .into_iter()
+                .map(non_empty)
+                .collect::<Result<_>>()?;
+        }
+        if let Some(card) = CARD_ROUTES.into_iter().find(|i| r.guards(i)) {
+            return Err(eyre!("admin routes can not guard the card endpoint {card}"));
+        }
+        Ok(r)
+    }
+
+    pub fn has_credentials(&self) -> bool {
+        self.token.is_some() || self.basic_auth.is_some()
+    }
+
+    /// whether `path` matches one of `routes`
+    pub fn guards(&self, path: &str) -> bool {
+        self.routes
+            .iter()
+            .any(|route| match route.strip_suffix("/*") {
+                Some(prefix) => path == prefix || path.starts_with(&format!("{prefix}/")),
+                None => path == route,
+            })
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[non_exhaustive]
 pub enum ListenStack {
@@ -45,7 +362,14 @@ pub enum ListenStack {
 
 impl Config {
     pub async fn init(path: impl AsRef<Path>) -> Result<Self> {
-        let config_str = read_to_string(path).await?;
+        read_to_string(path).await?.parse()
+    }
+}
+
+impl FromStr for Config {
+    type Err = color_eyre::Report;
+
+    fn from_str(config_str: &str) -> Result<Self> {
         let doc: KdlDocument = config_str.parse()?;
         let listen_stack = {
             let stack_str = doc
@@ -77,18 +401,50 @@ impl Config {
                     })
                 })
                 .unwrap_or_default(),
-            github_api_token: doc
-                .get_arg("github_api_token")
-                .and_then(|i| i.as_string())
+            github_api_tokens: doc
+                .get_args("github_api_token")
+                .into_iter()
+                .filter_map(|i| i.as_string())
                 .map(|i| i.to_string())
-                .expect("must provide github api token"),
+                .collect(),
             allow_users: doc
                 .get_args("allow_users")
                 .into_iter()
                 .filter_map(|i| i.as_string())
                 .map(|i| i.to_string())
                 .collect(),
+            cache: CacheConfig::from_kdl(doc.get("cache").and_then(|i| i.children()))?,
+            admin: AdminConfig::from_kdl(doc.get("admin").and_then(|i| i.children()))?,
+            github: GithubConfig::from_kdl(doc.get("github").and_then(|i| i.children()))?,
+            rank: RankConfig::from_kdl(doc.get("rank").and_then(|i| i.children()))?,
         };
+        if r.github_api_tokens.is_empty() {
+            return Err(eyre!("must provide github api token"));
+        }
         Ok(r)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin(config: &str) -> Result<AdminConfig> {
+        let doc: KdlDocument = config.parse()?;
+        AdminConfig::from_kdl(Some(&doc))
+    }
+
+    #[test]
+    fn test_admin_routes() {
+        let config = admin(r#"routes "/cache/*" "/status""#).unwrap();
+        assert!(config.guards("/cache"));
+        assert!(config.guards("/cache/keys"));
+        assert!(config.guards("/status"));
+        assert!(!config.guards("/cachekeys"));
+        assert!(!config.guards("/stats"));
+        // card endpoints are always public
+        for routes in [r#""/stats""#, r#""/stats/*""#, r#""/*""#, r#""/stats/pin""#] {
+            assert!(admin(&format!("routes {routes}")).is_err(), "{routes}");
+        }
+    }
+}
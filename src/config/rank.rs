@@ -0,0 +1,234 @@
+//! rank of the stats card, `rank { }` section
+
+use color_eyre::{eyre::eyre, Result};
+use kdl::{KdlDocument, KdlNode};
+use serde::{Deserialize, Serialize};
+
+/// how users are ranked, `rank_model=` overrides the configured one per request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankModel {
+    /// weighted sum of all stats through a normal cdf, the original algorithm
+    #[default]
+    Classic,
+    /// weighted exponential and log-normal cdfs of each stat against its median,
+    /// like the newer github-readme-stats
+    Percentile,
+}
+
+impl RankModel {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "classic" => Ok(Self::Classic),
+            "percentile" => Ok(Self::Percentile),
+            _ => Err(eyre!("unknown rank model: {name}")),
+        }
+    }
+}
+
+/// one value per stat of the stats card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatWeights {
+    pub commits: f64,
+    pub prs: f64,
+    pub issues: f64,
+    pub stars: f64,
+    pub followers: f64,
+    pub contribs: f64,
+    pub repos: f64,
+}
+
+impl StatWeights {
+    fn values(&self) -> [f64; 7] {
+        [
+            self.commits,
+            self.prs,
+            self.issues,
+            self.stars,
+            self.followers,
+            self.contribs,
+            self.repos,
+        ]
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.values().iter().sum()
+    }
+
+    /// override values by the properties of `node`, `commits=2 stars=4`
+    fn update(&mut self, node: &KdlNode) -> Result<()> {
+        for entry in node.entries() {
+            let Some(name) = entry.name().map(|i| i.value()) else {
+                continue;
+            };
+            let value = to_f64(entry.value())
+                .filter(|i| *i >= 0.)
+                .ok_or_else(|| eyre!("invalid rank {}: {name}", node.name().value()))?;
+            let field = match name {
+                "commits" => &mut self.commits,
+                "prs" => &mut self.prs,
+                "issues" => &mut self.issues,
+                "stars" => &mut self.stars,
+                "followers" => &mut self.followers,
+                "contribs" => &mut self.contribs,
+                "repos" => &mut self.repos,
+                _ => {
+                    return Err(eyre!(
+                        "unknown stat in rank {}: {name}",
+                        node.name().value()
+                    ))
+                }
+            };
+            *field = value;
+        }
+        Ok(())
+    }
+}
+
+/// a level is given to scores up to its threshold, below it in the classic
+/// model, the last one to the rest
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankLevel {
+    pub name: String,
+    pub threshold: f64,
+}
+
+impl RankLevel {
+    fn new(name: &str, threshold: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            threshold,
+        }
+    }
+
+    /// levels of `node` sorted by threshold, `"S+"=1 S=25`
+    fn from_node(node: &KdlNode) -> Result<Vec<Self>> {
+        let mut levels = node
+            .entries()
+            .iter()
+            .filter_map(|entry| Some((entry.name()?.value(), entry.value())))
+            .map(|(name, value)| {
+                to_f64(value)
+                    .map(|threshold| Self::new(name, threshold))
+                    .ok_or_else(|| eyre!("invalid threshold of rank level {name}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if levels.is_empty() {
+            return Err(eyre!("{} needs at least one level", node.name().value()));
+        }
+        levels.sort_by(|a, b| a.threshold.total_cmp(&b.threshold));
+        Ok(levels)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RankConfig {
+    /// default `classic`
+    pub model: RankModel,
+    /// offsets of each stat in the classic model
+    pub classic_weights: StatWeights,
+    /// default `S+` 1, `S` 25, `A++` 45, `A+` 60, `B+`
+    pub classic_levels: Vec<RankLevel>,
+    /// spread of the normal cdf in the classic model, default 231
+    pub classic_spread: f64,
+    /// weights of each stat in the percentile model
+    pub percentile_weights: StatWeights,
+    /// median of each stat in the percentile model
+    pub percentile_medians: StatWeights,
+    /// default `S` 1, `A+` 12.5, `A` 25, `A-` 37.5, `B+` 50, `B` 62.5, `B-` 75, `C+` 87.5, `C`
+    pub percentile_levels: Vec<RankLevel>,
+}
+
+impl Default for RankConfig {
+    fn default() -> Self {
+        Self {
+            model: RankModel::default(),
+            classic_weights: StatWeights {
+                commits: 1.65,
+                prs: 0.5,
+                issues: 1.,
+                stars: 0.75,
+                followers: 0.45,
+                contribs: 1.65,
+                repos: 1.,
+            },
+            classic_levels: vec![
+                RankLevel::new("S+", 1.),
+                RankLevel::new("S", 25.),
+                RankLevel::new("A++", 45.),
+                RankLevel::new("A+", 60.),
+                RankLevel::new("B+", 100.),
+            ],
+            classic_spread: 231.,
+            percentile_weights: StatWeights {
+                commits: 2.,
+                prs: 3.,
+                issues: 1.,
+                stars: 4.,
+                followers: 1.,
+                contribs: 0.,
+                repos: 0.,
+            },
+            percentile_medians: StatWeights {
+                commits: 250.,
+                prs: 50.,
+                issues: 25.,
+                stars: 50.,
+                followers: 10.,
+                contribs: 10.,
+                repos: 10.,
+            },
+            percentile_levels: vec![
+                RankLevel::new("S", 1.),
+                RankLevel::new("A+", 12.5),
+                RankLevel::new("A", 25.),
+                RankLevel::new("A-", 37.5),
+                RankLevel::new("B+", 50.),
+                RankLevel::new("B", 62.5),
+                RankLevel::new("B-", 75.),
+                RankLevel::new("C+", 87.5),
+                RankLevel::new("C", 100.),
+            ],
+        }
+    }
+}
+
+impl RankConfig {
+    pub(super) fn from_kdl(doc: Option<&KdlDocument>) -> Result<Self> {
+        let mut r = Self::default();
+        let Some(doc) = doc else {
+            return Ok(r);
+        };
+        if let Some(model) = doc.get_arg("model").and_then(|i| i.as_string()) {
+            r.model = RankModel::from_name(model)?;
+        }
+        if let Some(node) = doc.get("classic") {
+            r.classic_weights.update(node)?;
+        }
+        if let Some(node) = doc.get("classic_levels") {
+            r.classic_levels = RankLevel::from_node(node)?;
+        }
+        if let Some(spread) = doc.get_arg("classic_spread") {
+            r.classic_spread = to_f64(spread)
+                .filter(|i| *i > 0.)
+                .ok_or_else(|| eyre!("rank classic_spread must be positive"))?;
+        }
+        if let Some(node) = doc.get("percentile") {
+            r.percentile_weights.update(node)?;
+        }
+        if let Some(node) = doc.get("percentile_medians") {
+            r.percentile_medians.update(node)?;
+            if r.percentile_medians.values().contains(&0.) {
+                return Err(eyre!("rank percentile_medians must be positive"));
+            }
+        }
+        if let Some(node) = doc.get("percentile_levels") {
+            r.percentile_levels = RankLevel::from_node(node)?;
+        }
+        Ok(r)
+    }
+}
+
+fn to_f64(value: &kdl::KdlValue) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_i64().map(|i| i as f64))
+}
@@ -1,22 +1,38 @@
+use std::{fmt, sync::Arc};
+
 use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    Json,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use serde_json::json;
+use tracing::warn;
+
+use crate::{cards::form_error_card, config::Theme};
 
 /// Our app's top level error type.
-enum AppError {
+#[derive(Debug, Clone)]
+pub enum AppError {
+    /// A required query parameter is missing.
+    MissingParameter(&'static str),
+    /// Query parameters can not be parsed or are out of range.
+    InvalidParameter(String),
+    /// The user is not in `allow_users`.
+    NotAllowed,
     /// Something went wrong when calling the user repo.
     UserRepo(UserRepoError),
+    /// GitHub answered with GraphQL `errors`.
+    GraphQL(String),
+    /// GitHub API rate limit exceeded for our token.
+    RateLimited,
+    /// Failed to reach GitHub or to decode its response.
+    Transport(Arc<reqwest::Error>),
 }
 
 /// Errors that can happen when using the user repo.
-#[derive(Debug)]
-enum UserRepoError {
-    #[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum UserRepoError {
     NotFound,
-    #[allow(dead_code)]
+    RepositoryNotFound,
     InvalidUsername,
 }
 
@@ -28,21 +44,163 @@ impl From<UserRepoError> for AppError {
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+impl From<reqwest::Error> for AppError {
+    fn from(inner: reqwest::Error) -> Self {
+        AppError::Transport(Arc::new(inner))
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            AppError::MissingParameter(name) => return write!(f, "Missing {name} parameter"),
+            AppError::InvalidParameter(message) => message.as_str(),
+            AppError::NotAllowed => "User is not allowed",
+            AppError::UserRepo(UserRepoError::NotFound) => "User not found",
+            AppError::UserRepo(UserRepoError::RepositoryNotFound) => "Repository not found",
+            AppError::UserRepo(UserRepoError::InvalidUsername) => "Invalid username",
+            AppError::GraphQL(_) => "GitHub API returned an error",
+            AppError::RateLimited => "GitHub API rate limit exceeded",
+            AppError::Transport(_) => "Failed to reach GitHub API",
+        };
+        f.write_str(message)
+    }
+}
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::MissingParameter(_) => StatusCode::NOT_FOUND,
+            AppError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+            AppError::NotAllowed => StatusCode::FORBIDDEN,
+            AppError::UserRepo(UserRepoError::NotFound)
+            | AppError::UserRepo(UserRepoError::RepositoryNotFound) => StatusCode::NOT_FOUND,
+            AppError::UserRepo(UserRepoError::InvalidUsername) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::GraphQL(_) => StatusCode::BAD_GATEWAY,
+            AppError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Transport(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// secondary line of the error card, tells the user what to do next
+    pub fn hint(&self) -> &'static str {
+        match self {
+            AppError::MissingParameter(_) | AppError::InvalidParameter(_) => {
+                "Check the query string of the card url"
+            }
+            AppError::NotAllowed => "This instance only serves users in its allow list",
             AppError::UserRepo(UserRepoError::NotFound) => {
-                (StatusCode::NOT_FOUND, "User not found")
+                "Make sure the username is spelled right"
+            }
+            AppError::UserRepo(UserRepoError::RepositoryNotFound) => {
+                "Make sure the repository exists and is public"
             }
             AppError::UserRepo(UserRepoError::InvalidUsername) => {
-                (StatusCode::UNPROCESSABLE_ENTITY, "Invalid username")
+                "Usernames contain only letters, digits and single hyphens"
             }
-        };
+            AppError::GraphQL(_) | AppError::RateLimited | AppError::Transport(_) => {
+                "Please try again later"
+            }
+        }
+    }
+
+    /// render the error card with the requested theme
+    pub fn with_theme(self, theme: Theme) -> ErrorCard {
+        ErrorCard { error: self, theme }
+    }
+
+    /// `{"error", "hint"}` with the status code, for the json apis
+    pub fn into_json(self) -> Response {
+        self.log();
+        (
+            self.status_code(),
+            Json(json!({
+                "error": self.to_string(),
+                "hint": self.hint(),
+            })),
+        )
+            .into_response()
+    }
+
+    fn log(&self) {
+        match self {
+            AppError::GraphQL(messages) => warn!("github graphql errors: {}", messages),
+            AppError::RateLimited => warn!("github rate limit exceeded"),
+            AppError::Transport(e) => warn!("github request failed: {}", e),
+            _ => {}
+        }
+    }
+}
+
+/// an `AppError` rendered as a themed svg card
+pub struct ErrorCard {
+    error: AppError,
+    theme: Theme,
+}
+
+/// render an error card with the default theme
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        self.with_theme(Theme::default()).into_response()
+    }
+}
+
+/// render an error card, so embedded images show what went wrong
+impl IntoResponse for ErrorCard {
+    fn into_response(self) -> Response {
+        let error = self.error;
+        error.log();
+
+        (
+            error.status_code(),
+            [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
+            form_error_card(&error.to_string(), error.hint(), self.theme).to_string(),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+    #[test]
+    fn test_error_card() {
+        let transport = reqwest::Client::new().get("not a url").build().unwrap_err();
+        for (error, status) in [
+            (AppError::MissingParameter("user"), StatusCode::NOT_FOUND),
+            (
+                AppError::InvalidParameter("bad".to_string()),
+                StatusCode::BAD_REQUEST,
+            ),
+            (AppError::NotAllowed, StatusCode::FORBIDDEN),
+            (UserRepoError::NotFound.into(), StatusCode::NOT_FOUND),
+            (
+                UserRepoError::RepositoryNotFound.into(),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                UserRepoError::InvalidUsername.into(),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                AppError::GraphQL("oops".to_string()),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (AppError::RateLimited, StatusCode::TOO_MANY_REQUESTS),
+            (transport.into(), StatusCode::SERVICE_UNAVAILABLE),
+        ] {
+            let response = error.clone().with_theme(Theme::default()).into_response();
+            assert_eq!(response.status(), status, "{error:?}");
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "image/svg+xml; charset=utf-8",
+                "{error:?}"
+            );
 
-        (status, body).into_response()
+            let response = error.into_json();
+            assert_eq!(response.status(), status);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        }
     }
 }
@@ -0,0 +1,83 @@
+use bincode::{Decode, Encode};
+use serde::Serialize;
+
+use super::{gen::contribution_calendar, GithubClient};
+use crate::{
+    error::{AppError, UserRepoError},
+    utils::{MonitorTime, SystemTimeWrapper},
+};
+
+pub async fn query_contribution_calendar(
+    client: &GithubClient,
+    variables: contribution_calendar::Variables,
+) -> Result<contribution_calendar::ResponseData, AppError> {
+    client
+        .query::<contribution_calendar::ContributionCalendar>(variables)
+        .await
+}
+
+#[derive(Debug, Clone, Decode, Encode, Serialize)]
+pub struct ContributionDay {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub count: i64,
+    /// 0 is Sunday
+    pub weekday: u8,
+}
+
+#[derive(Debug, Clone, Default, Decode, Encode, Serialize)]
+pub struct ContributionCalendar {
+    pub login: String,
+    pub name: String,
+    /// last year if `None`
+    pub year: Option<i32>,
+    pub total_contributions: i64,
+    pub weeks: Vec<Vec<ContributionDay>>,
+    #[serde(skip)]
+    pub(crate) __create_at: SystemTimeWrapper,
+}
+
+impl MonitorTime for ContributionCalendar {
+    fn create_at(&self) -> SystemTimeWrapper {
+        self.__create_at
+    }
+}
+
+pub async fn get_contribution_calendar(
+    client: &GithubClient,
+    username: &str,
+    year: Option<i32>,
+) -> Result<ContributionCalendar, AppError> {
+    let variables = contribution_calendar::Variables {
+        login: username.to_string(),
+        from: year.map(|y| format!("{y}-01-01T00:00:00Z")),
+        to: year.map(|y| format!("{y}-12-31T23:59:59Z")),
+    };
+    let data = query_contribution_calendar(client, variables).await?;
+    let user = data.user.ok_or(UserRepoError::NotFound)?;
+    let calendar = user.contributions_collection.contribution_calendar;
+
+    let weeks = calendar
+        .weeks
+        .into_iter()
+        .map(|week| {
+            week.contribution_days
+                .into_iter()
+                .map(|day| ContributionDay {
+                    date: day.date,
+                    count: day.contribution_count,
+                    weekday: day.weekday as u8,
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(ContributionCalendar {
+        login: user.login.clone(),
+        name: user.name.unwrap_or(user.login),
+        year,
+        total_contributions: calendar.total_contributions,
+        weeks,
+        __create_at: SystemTimeWrapper::default(),
+    })
+}
@@ -0,0 +1,72 @@
+pub struct ContributionCalendar;
+
+pub const OPERATION_NAME: &str = "ContributionCalendar";
+pub const QUERY: &str = "query ContributionCalendar($login: String!, $from: DateTime, $to: DateTime) {\n  rateLimit {\n    limit\n    remaining\n    resetAt\n  }\n  user(login: $login) {\n    name\n    login\n    contributionsCollection(from: $from, to: $to) {\n      contributionCalendar {\n        totalContributions\n        weeks {\n          contributionDays {\n            contributionCount\n            date\n            weekday\n          }\n        }\n      }\n    }\n  }\n}\n";
+use serde::{Deserialize, Serialize};
+
+use super::*;
+#[allow(dead_code)]
+type Boolean = bool;
+#[allow(dead_code)]
+type Float = f64;
+#[allow(dead_code)]
+type Int = i64;
+#[allow(dead_code)]
+type ID = String;
+#[derive(Serialize, Debug)]
+pub struct Variables {
+    pub login: String,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+}
+impl Variables {}
+#[derive(Deserialize, Debug)]
+pub struct ResponseData {
+    pub user: Option<ContributionCalendarUser>,
+}
+#[derive(Deserialize, Debug)]
+pub struct ContributionCalendarUser {
+    pub name: Option<String>,
+    pub login: String,
+    #[serde(rename = "contributionsCollection")]
+    pub contributions_collection: ContributionCalendarUserContributionsCollection,
+}
+#[derive(Deserialize, Debug)]
+pub struct ContributionCalendarUserContributionsCollection {
+    #[serde(rename = "contributionCalendar")]
+    pub contribution_calendar: ContributionCalendarUserContributionsCollectionContributionCalendar,
+}
+#[derive(Deserialize, Debug)]
+pub struct ContributionCalendarUserContributionsCollectionContributionCalendar {
+    #[serde(rename = "totalContributions")]
+    pub total_contributions: Int,
+    pub weeks: Vec<ContributionCalendarUserContributionsCollectionContributionCalendarWeeks>,
+}
+#[derive(Deserialize, Debug)]
+pub struct ContributionCalendarUserContributionsCollectionContributionCalendarWeeks {
+    #[serde(rename = "contributionDays")]
+    pub contribution_days: Vec<
+        ContributionCalendarUserContributionsCollectionContributionCalendarWeeksContributionDays,
+    >,
+}
+#[derive(Deserialize, Debug)]
+pub struct ContributionCalendarUserContributionsCollectionContributionCalendarWeeksContributionDays
+{
+    #[serde(rename = "contributionCount")]
+    pub contribution_count: Int,
+    pub date: Date,
+    pub weekday: Int,
+}
+
+impl graphql_client::GraphQLQuery for ContributionCalendar {
+    type ResponseData = contribution_calendar::ResponseData;
+    type Variables = contribution_calendar::Variables;
+
+    fn build_query(variables: Self::Variables) -> ::graphql_client::QueryBody<Self::Variables> {
+        graphql_client::QueryBody {
+            variables,
+            query: contribution_calendar::QUERY,
+            operation_name: contribution_calendar::OPERATION_NAME,
+        }
+    }
+}
@@ -1,3 +1,11 @@
+pub mod contribution_calendar;
+pub mod repo;
 pub mod top_langs;
 pub mod user_info;
 pub mod user_repos;
+pub mod user_streak;
+
+/// custom scalar, an ISO-8601 encoded date string
+type Date = String;
+/// custom scalar, an ISO-8601 encoded UTC date string
+type DateTime = String;
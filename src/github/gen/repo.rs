@@ -0,0 +1,69 @@
+pub struct Repo;
+
+pub const OPERATION_NAME: &str = "Repo";
+pub const QUERY: &str = "query Repo($login: String!, $repo: String!) {\n  rateLimit {\n    limit\n    remaining\n    resetAt\n  }\n  # works for both users and organizations\n  repositoryOwner(login: $login) {\n    repository(name: $repo) {\n      name\n      nameWithOwner\n      description\n      isArchived\n      isTemplate\n      stargazers {\n        totalCount\n      }\n      forkCount\n      primaryLanguage {\n        color\n        name\n      }\n    }\n  }\n}\n";
+use serde::{Deserialize, Serialize};
+
+use super::*;
+#[allow(dead_code)]
+type Boolean = bool;
+#[allow(dead_code)]
+type Float = f64;
+#[allow(dead_code)]
+type Int = i64;
+#[allow(dead_code)]
+type ID = String;
+#[derive(Serialize, Debug)]
+pub struct Variables {
+    pub login: String,
+    pub repo: String,
+}
+impl Variables {}
+#[derive(Deserialize, Debug)]
+pub struct ResponseData {
+    #[serde(rename = "repositoryOwner")]
+    pub repository_owner: Option<RepoRepositoryOwner>,
+}
+#[derive(Deserialize, Debug)]
+pub struct RepoRepositoryOwner {
+    pub repository: Option<RepoRepositoryOwnerRepository>,
+}
+#[derive(Deserialize, Debug)]
+pub struct RepoRepositoryOwnerRepository {
+    pub name: String,
+    #[serde(rename = "nameWithOwner")]
+    pub name_with_owner: String,
+    pub description: Option<String>,
+    #[serde(rename = "isArchived")]
+    pub is_archived: Boolean,
+    #[serde(rename = "isTemplate")]
+    pub is_template: Boolean,
+    pub stargazers: RepoRepositoryOwnerRepositoryStargazers,
+    #[serde(rename = "forkCount")]
+    pub fork_count: Int,
+    #[serde(rename = "primaryLanguage")]
+    pub primary_language: Option<RepoRepositoryOwnerRepositoryPrimaryLanguage>,
+}
+#[derive(Deserialize, Debug)]
+pub struct RepoRepositoryOwnerRepositoryStargazers {
+    #[serde(rename = "totalCount")]
+    pub total_count: Int,
+}
+#[derive(Deserialize, Debug)]
+pub struct RepoRepositoryOwnerRepositoryPrimaryLanguage {
+    pub color: Option<String>,
+    pub name: String,
+}
+
+impl graphql_client::GraphQLQuery for Repo {
+    type ResponseData = repo::ResponseData;
+    type Variables = repo::Variables;
+
+    fn build_query(variables: Self::Variables) -> ::graphql_client::QueryBody<Self::Variables> {
+        graphql_client::QueryBody {
+            variables,
+            query: repo::QUERY,
+            operation_name: repo::OPERATION_NAME,
+        }
+    }
+}
@@ -1,7 +1,7 @@
 pub struct TopLang;
 
 pub const OPERATION_NAME: &str = "TopLang";
-pub const QUERY: &str = "query TopLang($login: String!) {\n  user(login: $login) {\n    # fetch only owner repos & not forks\n    repositories(ownerAffiliations: OWNER, isFork: false, first: 100) {\n      nodes {\n        name\n        languages(first: 10, orderBy: { field: SIZE, direction: DESC }) {\n          edges {\n            size\n            node {\n              color\n              name\n            }\n          }\n        }\n      }\n    }\n  }\n}\n";
+pub const QUERY: &str = "query TopLang($login: String!) {\n  rateLimit {\n    limit\n    remaining\n    resetAt\n  }\n  user(login: $login) {\n    # fetch only owner repos & not forks\n    repositories(ownerAffiliations: OWNER, isFork: false, first: 100) {\n      nodes {\n        name\n        languages(first: 10, orderBy: { field: SIZE, direction: DESC }) {\n          edges {\n            size\n            node {\n              color\n              name\n            }\n          }\n        }\n      }\n    }\n  }\n}\n";
 use serde::{Deserialize, Serialize};
 
 use super::*;
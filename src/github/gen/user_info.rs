@@ -1,7 +1,7 @@
 pub struct UserInfo;
 
 pub const OPERATION_NAME: &str = "UserInfo";
-pub const QUERY: &str = "query UserInfo($login: String!) {\n  user(login: $login) {\n    name\n    login\n    contributionsCollection {\n      totalCommitContributions\n      restrictedContributionsCount\n    }\n    repositoriesContributedTo(\n      contributionTypes: [COMMIT, ISSUE, PULL_REQUEST, REPOSITORY]\n    ) {\n      totalCount\n    }\n    pullRequests {\n      totalCount\n    }\n    openIssues: issues(states: OPEN) {\n      totalCount\n    }\n    closedIssues: issues(states: CLOSED) {\n      totalCount\n    }\n    followers {\n      totalCount\n    }\n    repositories(ownerAffiliations: OWNER) {\n      totalCount\n    }\n  }\n}\n";
+pub const QUERY: &str = "query UserInfo($login: String!) {\n  rateLimit {\n    limit\n    remaining\n    resetAt\n  }\n  user(login: $login) {\n    name\n    login\n    contributionsCollection {\n      totalCommitContributions\n      restrictedContributionsCount\n    }\n    repositoriesContributedTo(\n      contributionTypes: [COMMIT, ISSUE, PULL_REQUEST, REPOSITORY]\n    ) {\n      totalCount\n    }\n    pullRequests {\n      totalCount\n    }\n    openIssues: issues(states: OPEN) {\n      totalCount\n    }\n    closedIssues: issues(states: CLOSED) {\n      totalCount\n    }\n    followers {\n      totalCount\n    }\n    repositories(ownerAffiliations: OWNER) {\n      totalCount\n    }\n  }\n}\n";
 use serde::{Deserialize, Serialize};
 
 use super::*;
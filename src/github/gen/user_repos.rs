@@ -1,7 +1,7 @@
 pub struct UserRepo;
 
 pub const OPERATION_NAME: &str = "UserRepo";
-pub const QUERY: &str = "query UserRepo($login: String!, $after: String) {\n  user(login: $login) {\n    repositories(\n      first: 100\n      ownerAffiliations: OWNER\n      orderBy: { direction: DESC, field: STARGAZERS }\n      after: $after\n    ) {\n      nodes {\n        name\n        stargazers {\n          totalCount\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n}\n";
+pub const QUERY: &str = "query UserRepo($login: String!, $after: String) {\n  rateLimit {\n    limit\n    remaining\n    resetAt\n  }\n  user(login: $login) {\n    repositories(\n      first: 100\n      ownerAffiliations: OWNER\n      orderBy: { direction: DESC, field: STARGAZERS }\n      after: $after\n    ) {\n      nodes {\n        name\n        stargazers {\n          totalCount\n        }\n      }\n      pageInfo {\n        hasNextPage\n        endCursor\n      }\n    }\n  }\n}\n";
 use serde::{Deserialize, Serialize};
 
 #[allow(dead_code)]
@@ -0,0 +1,71 @@
+pub struct UserStreak;
+
+pub const OPERATION_NAME: &str = "UserStreak";
+pub const QUERY: &str = "query UserStreak(\n  $login: String!\n  $from: DateTime\n  $to: DateTime\n  $withCalendar: Boolean!\n) {\n  rateLimit {\n    limit\n    remaining\n    resetAt\n  }\n  user(login: $login) {\n    name\n    login\n    contributionsCollection(from: $from, to: $to) {\n      contributionYears\n      contributionCalendar @include(if: $withCalendar) {\n        weeks {\n          contributionDays {\n            contributionCount\n            date\n          }\n        }\n      }\n    }\n  }\n}\n";
+use serde::{Deserialize, Serialize};
+
+use super::*;
+#[allow(dead_code)]
+type Boolean = bool;
+#[allow(dead_code)]
+type Float = f64;
+#[allow(dead_code)]
+type Int = i64;
+#[allow(dead_code)]
+type ID = String;
+#[derive(Serialize, Debug)]
+pub struct Variables {
+    pub login: String,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+    #[serde(rename = "withCalendar")]
+    pub with_calendar: Boolean,
+}
+impl Variables {}
+#[derive(Deserialize, Debug)]
+pub struct ResponseData {
+    pub user: Option<UserStreakUser>,
+}
+#[derive(Deserialize, Debug)]
+pub struct UserStreakUser {
+    pub name: Option<String>,
+    pub login: String,
+    #[serde(rename = "contributionsCollection")]
+    pub contributions_collection: UserStreakUserContributionsCollection,
+}
+#[derive(Deserialize, Debug)]
+pub struct UserStreakUserContributionsCollection {
+    #[serde(rename = "contributionYears")]
+    pub contribution_years: Vec<Int>,
+    #[serde(rename = "contributionCalendar")]
+    pub contribution_calendar: Option<UserStreakUserContributionsCollectionContributionCalendar>,
+}
+#[derive(Deserialize, Debug)]
+pub struct UserStreakUserContributionsCollectionContributionCalendar {
+    pub weeks: Vec<UserStreakUserContributionsCollectionContributionCalendarWeeks>,
+}
+#[derive(Deserialize, Debug)]
+pub struct UserStreakUserContributionsCollectionContributionCalendarWeeks {
+    #[serde(rename = "contributionDays")]
+    pub contribution_days:
+        Vec<UserStreakUserContributionsCollectionContributionCalendarWeeksContributionDays>,
+}
+#[derive(Deserialize, Debug)]
+pub struct UserStreakUserContributionsCollectionContributionCalendarWeeksContributionDays {
+    #[serde(rename = "contributionCount")]
+    pub contribution_count: Int,
+    pub date: Date,
+}
+
+impl graphql_client::GraphQLQuery for UserStreak {
+    type ResponseData = user_streak::ResponseData;
+    type Variables = user_streak::Variables;
+
+    fn build_query(variables: Self::Variables) -> ::graphql_client::QueryBody<Self::Variables> {
+        graphql_client::QueryBody {
+            variables,
+            query: user_streak::QUERY,
+            operation_name: user_streak::OPERATION_NAME,
+        }
+    }
+}
@@ -1,25 +1,459 @@
-use color_eyre::Result;
+use std::{
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicI64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use chrono::DateTime;
+use graphql_client::{GraphQLQuery, Response};
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::time;
+use tracing::{debug, trace, warn};
+
+use crate::{
+    config::GithubConfig,
+    error::{AppError, UserRepoError},
+    humantime::HumanTime,
+};
+
+pub mod calendar;
 pub mod gen;
+pub mod repo;
 pub mod stats;
+pub mod streak;
 pub mod top_langs;
 
 pub use stats::get_user_github_stats;
 
-const GITHUB_API: &str = "https://api.github.com/graphql";
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+const LOGIN_MAX_LENGTH: usize = 39;
+/// retries of transient errors and secondary rate limits
+const MAX_RETRIES: u32 = 3;
+/// doubled on every retry, the delay is a random part of it
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// a query and its retries give up after this, well within the client timeout,
+/// stale cached data is served instead if any
+const MAX_RETRY_TIME: Duration = Duration::from_secs(4);
+/// how long a token is skipped when github reports its rate limit in the body only
+const EXHAUSTED_BACKOFF: i64 = 60;
+/// `X-RateLimit-Remaining` and `X-RateLimit-Limit` before the first response
+const UNKNOWN: i64 = i64::MAX;
+
+/// github api tokens of `github_api_token`, requests go to the one with the most
+/// rate limit left
+#[derive(Clone)]
+struct TokenPool {
+    tokens: Arc<[TokenState]>,
+    /// breaks ties between tokens, so unused ones are tried in turn
+    next: Arc<AtomicUsize>,
+}
+
+struct TokenState {
+    token: String,
+    /// `X-RateLimit-Remaining` of the last response
+    remaining: AtomicI64,
+    /// `X-RateLimit-Limit` of the last response
+    limit: AtomicI64,
+    /// `X-RateLimit-Reset`, unix seconds
+    reset_at: AtomicI64,
+}
+
+/// rate limit of a token, shown on the status page
+#[derive(Debug, Serialize)]
+pub struct TokenBudget {
+    pub remaining: Option<i64>,
+    pub limit: Option<i64>,
+    /// seconds until the limit is full again
+    pub reset_in: Option<u64>,
+}
+
+impl TokenBudget {
+    pub fn remaining_string(&self) -> String {
+        match (self.remaining, self.limit) {
+            (Some(remaining), Some(limit)) => format!("{remaining} / {limit}"),
+            (Some(remaining), None) => remaining.to_string(),
+            _ => "unknown".to_string(),
+        }
+    }
+
+    pub fn reset_in_string(&self) -> String {
+        self.reset_in
+            .map(|i| format!("{:#}", HumanTime::from(Duration::from_secs(i))))
+            .unwrap_or_else(|| "-".to_string())
+    }
+}
+
+impl fmt::Debug for TokenPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenPool")
+            .field("tokens", &self.tokens.len())
+            .finish()
+    }
+}
+
+impl TokenPool {
+    fn new(tokens: &[String]) -> Self {
+        let tokens = tokens
+            .iter()
+            .map(|token| TokenState {
+                token: token.clone(),
+                remaining: AtomicI64::new(UNKNOWN),
+                limit: AtomicI64::new(UNKNOWN),
+                reset_at: AtomicI64::new(0),
+            })
+            .collect();
+        Self {
+            tokens,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// index of the token with the most requests left, its limit is full again after reset,
+    /// `None` if all of them are exhausted
+    fn pick(&self) -> Option<usize> {
+        let now = unix_now();
+        let len = self.tokens.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        (0..len)
+            .map(|i| (start + i) % len)
+            .map(|i| (i, self.remaining_of(i, now)))
+            .filter(|&(_, remaining)| remaining > 0)
+            .max_by_key(|&(_, remaining)| remaining)
+            .map(|(i, _)| i)
+    }
+
+    fn remaining_of(&self, idx: usize, now: i64) -> i64 {
+        let token = &self.tokens[idx];
+        if token.reset_at.load(Ordering::Relaxed) <= now {
+            UNKNOWN
+        } else {
+            token.remaining.load(Ordering::Relaxed)
+        }
+    }
+
+    /// record the rate limit headers of a response to token `idx`
+    fn update(&self, idx: usize, headers: &HeaderMap) {
+        let get = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|i| i.to_str().ok())
+                .and_then(|i| i.parse::<i64>().ok())
+        };
+        let (Some(remaining), Some(reset_at)) =
+            (get("x-ratelimit-remaining"), get("x-ratelimit-reset"))
+        else {
+            return;
+        };
+        self.record(idx, remaining, get("x-ratelimit-limit"), reset_at);
+    }
+
+    fn record(&self, idx: usize, remaining: i64, limit: Option<i64>, reset_at: i64) {
+        let token = &self.tokens[idx];
+        token.remaining.store(remaining, Ordering::Relaxed);
+        token.reset_at.store(reset_at, Ordering::Relaxed);
+        if let Some(limit) = limit {
+            token.limit.store(limit, Ordering::Relaxed);
+        }
+        debug!("github token #{}: {} requests left", idx, remaining);
+    }
+
+    /// skip token `idx` until its reset, or for a while if the reset is unknown
+    fn exhaust(&self, idx: usize) {
+        let now = unix_now();
+        let token = &self.tokens[idx];
+        token.remaining.store(0, Ordering::Relaxed);
+        if token.reset_at.load(Ordering::Relaxed) <= now {
+            token
+                .reset_at
+                .store(now + EXHAUSTED_BACKOFF, Ordering::Relaxed);
+        }
+    }
+
+    fn budget(&self) -> Vec<TokenBudget> {
+        let now = unix_now();
+        let known = |i: i64| (i != UNKNOWN).then_some(i);
+        (0..self.tokens.len())
+            .map(|idx| {
+                let reset_at = self.tokens[idx].reset_at.load(Ordering::Relaxed);
+                TokenBudget {
+                    remaining: known(self.remaining_of(idx, now)),
+                    limit: known(self.tokens[idx].limit.load(Ordering::Relaxed)),
+                    reset_in: (reset_at > now).then(|| (reset_at - now) as u64),
+                }
+            })
+            .collect()
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|i| i.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// runs graphql queries with the tokens of the pool, built once and shared by all
+/// requests to reuse connections
+#[derive(Debug, Clone)]
+pub struct GithubClient {
+    client: reqwest::Client,
+    api_url: String,
+    pool: TokenPool,
+}
+
+/// a failed request
+enum Attempt {
+    /// worth another try, after the given delay or a backoff
+    Retry(AppError, Option<Duration>),
+    Fatal(AppError),
+}
+
+/// the `rateLimit` object queries ask for next to their data
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RateLimit {
+    limit: Option<i64>,
+    remaining: i64,
+    reset_at: String,
+}
+
+impl GithubClient {
+    pub fn new(tokens: &[String], config: &GithubConfig) -> reqwest::Result<Self> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout);
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            api_url: config.api_url.clone(),
+            pool: TokenPool::new(tokens),
+        })
+    }
+
+    /// rate limit of every token, in the order of `github_api_token`
+    pub fn budget(&self) -> Vec<TokenBudget> {
+        self.pool.budget()
+    }
+
+    /// run a query with the token that has the most rate limit left:
+    /// - timeouts and 5xx are retried with jittered backoff
+    /// - secondary rate limits are retried after `Retry-After`
+    /// - an exhausted token is retried with another one
+    ///
+    /// no retry is waited for past `MAX_RETRY_TIME`
+    pub async fn query<Q>(&self, variables: Q::Variables) -> Result<Q::ResponseData, AppError>
+    where
+        Q: GraphQLQuery,
+        Q::ResponseData: Debug,
+    {
+        let body = Q::build_query(variables);
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let idx = self.pool.pick().ok_or(AppError::RateLimited)?;
+            let (error, delay) = match self.send(idx, &body).await {
+                Ok(data) => return Ok(data),
+                Err(Attempt::Retry(error, delay)) if attempt < MAX_RETRIES => (error, delay),
+                Err(Attempt::Retry(error, _) | Attempt::Fatal(error)) => return Err(error),
+            };
+            let delay = delay.unwrap_or_else(|| backoff(attempt));
+            if started.elapsed() + delay > MAX_RETRY_TIME {
+                return Err(error);
+            }
+            warn!(
+                "github {} failed: {}, retry in {:?}",
+                body.operation_name, error, delay
+            );
+            time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send<B, T>(&self, idx: usize, body: &B) -> Result<T, Attempt>
+    where
+        B: Serialize,
+        T: Debug + DeserializeOwned,
+    {
+        let res = self
+            .client
+            .post(&self.api_url)
+            .bearer_auth(&self.pool.tokens[idx].token)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() || e.is_connect() {
+                    Attempt::Retry(e.into(), None)
+                } else {
+                    Attempt::Fatal(e.into())
+                }
+            })?;
+        self.pool.update(idx, res.headers());
+
+        let status = res.status();
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::FORBIDDEN {
+            if let Some(retry_after) = retry_after(res.headers()) {
+                // secondary rate limit, too long waits fail in `query`
+                return Err(Attempt::Retry(AppError::RateLimited, Some(retry_after)));
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS
+                || self.pool.remaining_of(idx, unix_now()) == 0
+            {
+                return Err(self.exhausted(idx));
+            }
+        }
+        if status.is_server_error() {
+            let error = res.error_for_status().unwrap_err();
+            return Err(Attempt::Retry(error.into(), None));
+        }
+
+        match self.parse_response(idx, res).await {
+            Err(AppError::RateLimited) => Err(self.exhausted(idx)),
+            result => result.map_err(Attempt::Fatal),
+        }
+    }
+
+    /// retry right away with another token, if there is one with requests left
+    fn exhausted(&self, idx: usize) -> Attempt {
+        self.pool.exhaust(idx);
+        warn!("github token #{} is rate limited", idx);
+        if self.pool.pick().is_some() {
+            Attempt::Retry(AppError::RateLimited, Some(Duration::ZERO))
+        } else {
+            Attempt::Fatal(AppError::RateLimited)
+        }
+    }
+
+    /// decode a graphql response, map rate limits and `errors` to `AppError`
+    async fn parse_response<T>(&self, idx: usize, res: reqwest::Response) -> Result<T, AppError>
+    where
+        T: Debug + DeserializeOwned,
+    {
+        let body: serde_json::Value = res.error_for_status()?.json().await?;
+        let rate_limit = body
+            .pointer("/data/rateLimit")
+            .and_then(|i| RateLimit::deserialize(i).ok());
+        if let Some(rate_limit) = rate_limit {
+            if let Ok(reset_at) = DateTime::parse_from_rfc3339(&rate_limit.reset_at) {
+                self.pool.record(
+                    idx,
+                    rate_limit.remaining,
+                    rate_limit.limit,
+                    reset_at.timestamp(),
+                );
+            }
+        }
+
+        let response_body: Response<T> = serde_json::from_value(body)
+            .map_err(|e| AppError::GraphQL(format!("invalid response: {e}")))?;
+        trace!("{:#?}", response_body);
+        if let Some(errors) = response_body.errors.filter(|i| !i.is_empty()) {
+            let messages = errors
+                .iter()
+                .map(|i| i.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+            if messages.contains("rate limit") {
+                return Err(AppError::RateLimited);
+            }
+            // missing users come with `data.user: null`, callers turn it into `NotFound`
+            if response_body.data.is_none()
+                || !errors
+                    .iter()
+                    .all(|i| i.message.starts_with("Could not resolve to"))
+            {
+                return Err(AppError::GraphQL(messages));
+            }
+        }
+
+        response_body
+            .data
+            .ok_or_else(|| AppError::GraphQL("empty response".to_string()))
+    }
+}
+
+/// `Retry-After` in seconds
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|i| i.to_str().ok())
+        .and_then(|i| i.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// exponential backoff with full jitter, random between 0 and `RETRY_BASE_DELAY * 2^attempt`
+fn backoff(attempt: u32) -> Duration {
+    let cap = (RETRY_BASE_DELAY * 2u32.pow(attempt)).as_millis() as u64;
+    Duration::from_millis(RandomState::new().build_hasher().finish() % (cap + 1))
+}
+
+/// github logins are alphanumeric with single hyphens in between
+pub fn validate_login(login: &str) -> Result<(), UserRepoError> {
+    let valid = !login.is_empty()
+        && login.len() <= LOGIN_MAX_LENGTH
+        && !login.starts_with('-')
+        && !login.ends_with('-')
+        && !login.contains("--")
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(UserRepoError::InvalidUsername)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_pool_pick() {
+        let pool = TokenPool::new(&["a".to_string(), "b".to_string()]);
+        // unknown limits are tried in turn
+        assert_ne!(pool.pick(), pool.pick());
+
+        let reset_at = (unix_now() + 60).to_string();
+        let headers = |remaining: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-ratelimit-remaining", remaining.parse().unwrap());
+            headers.insert("x-ratelimit-reset", reset_at.parse().unwrap());
+            headers
+        };
+        pool.update(0, &headers("10"));
+        pool.update(1, &headers("4000"));
+        assert_eq!(pool.pick(), Some(1));
+        assert_eq!(pool.pick(), Some(1));
+
+        // limits are full again after reset
+        pool.tokens[1].reset_at.store(0, Ordering::Relaxed);
+        pool.update(0, &headers("0"));
+        assert_eq!(pool.pick(), Some(1));
+
+        // nothing left until reset
+        pool.exhaust(1);
+        assert_eq!(pool.pick(), None);
+    }
 
-pub fn build_client(token: &str) -> Result<reqwest::Client> {
-    let client = reqwest::Client::builder()
-        .user_agent(USER_AGENT)
-        .default_headers(
-            std::iter::once((
-                reqwest::header::AUTHORIZATION,
-                reqwest::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .collect(),
-        )
-        .build()?;
-
-    Ok(client)
+    #[test]
+    fn test_backoff() {
+        for attempt in 0..MAX_RETRIES {
+            let cap = RETRY_BASE_DELAY * 2u32.pow(attempt);
+            assert!((0..100).all(|_| backoff(attempt) <= cap));
+        }
+        // worst case of all retries fits in the budget
+        let worst: Duration = (0..MAX_RETRIES)
+            .map(|i| RETRY_BASE_DELAY * 2u32.pow(i))
+            .sum();
+        assert!(worst < MAX_RETRY_TIME);
+    }
 }
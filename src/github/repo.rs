@@ -0,0 +1,66 @@
+use bincode::{Decode, Encode};
+use serde::Serialize;
+
+use super::{gen::repo, GithubClient};
+use crate::{
+    error::{AppError, UserRepoError},
+    utils::{MonitorTime, SystemTimeWrapper},
+};
+
+pub async fn query_repo(
+    client: &GithubClient,
+    variables: repo::Variables,
+) -> Result<repo::ResponseData, AppError> {
+    client.query::<repo::Repo>(variables).await
+}
+
+#[derive(Debug, Clone, Default, Decode, Encode, Serialize)]
+pub struct RepoInfo {
+    pub name: String,
+    pub name_with_owner: String,
+    pub description: Option<String>,
+    pub is_archived: bool,
+    pub is_template: bool,
+    pub stars: i64,
+    pub forks: i64,
+    pub language: Option<String>,
+    pub language_color: Option<String>,
+    #[serde(skip)]
+    pub(crate) __create_at: SystemTimeWrapper,
+}
+
+impl MonitorTime for RepoInfo {
+    fn create_at(&self) -> SystemTimeWrapper {
+        self.__create_at
+    }
+}
+
+pub async fn get_repo_info(
+    client: &GithubClient,
+    username: &str,
+    repo_name: &str,
+) -> Result<RepoInfo, AppError> {
+    let variables = repo::Variables {
+        login: username.to_string(),
+        repo: repo_name.to_string(),
+    };
+    let data = query_repo(client, variables).await?;
+    let repo = data
+        .repository_owner
+        .ok_or(UserRepoError::NotFound)?
+        .repository
+        .ok_or(UserRepoError::RepositoryNotFound)?;
+
+    Ok(RepoInfo {
+        name: repo.name,
+        name_with_owner: repo.name_with_owner,
+        description: repo.description,
+        is_archived: repo.is_archived,
+        is_template: repo.is_template,
+        stars: repo.stargazers.total_count,
+        forks: repo.fork_count,
+        language: repo.primary_language.as_ref().map(|i| i.name.clone()),
+        language_color: repo.primary_language.and_then(|i| i.color),
+        __create_at: SystemTimeWrapper::default(),
+    })
+}
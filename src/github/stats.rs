@@ -1,17 +1,18 @@
 use bincode::{Decode, Encode};
-use color_eyre::Result;
-use graphql_client::{GraphQLQuery, Response};
-use reqwest::Client;
+use serde::Serialize;
 use tracing::trace;
 
 use super::{
-    build_client,
     gen::{user_info, user_repos},
-    GITHUB_API,
+    GithubClient,
+};
+use crate::{
+    config::{RankConfig, RankModel, StatWeights},
+    error::{AppError, UserRepoError},
+    utils::{MonitorTime, SystemTimeWrapper},
 };
-use crate::utils::{MonitorTime, SystemTimeWrapper};
 
-#[derive(Debug, Clone, Default, Decode, Encode)]
+#[derive(Debug, Clone, Default, Decode, Encode, Serialize)]
 pub struct UserGithubStats {
     pub login: String,
     pub name: String,
@@ -21,8 +22,10 @@ pub struct UserGithubStats {
     pub prs: i64,
     pub issues: i64,
     pub contribs: i64,
+    /// contributions to private repositories, not in `commits`
+    pub private_contribs: i64,
     pub followers: i64,
-    pub rank: Rank,
+    #[serde(skip)]
     pub(crate) __create_at: SystemTimeWrapper,
 }
 
@@ -33,74 +36,88 @@ impl MonitorTime for UserGithubStats {
 }
 
 impl UserGithubStats {
-    fn calculate_rank(&self) -> Rank {
-        const COMMITS_OFFSET: f64 = 1.65;
-        const CONTRIBS_OFFSET: f64 = 1.65;
-        const ISSUES_OFFSET: f64 = 1.;
-        const STARS_OFFSET: f64 = 0.75;
-        const PRS_OFFSET: f64 = 0.5;
-        const FOLLOWERS_OFFSET: f64 = 0.45;
-        const REPO_OFFSET: f64 = 1.;
-
-        const ALL_OFFSETS: f64 = CONTRIBS_OFFSET
-            + ISSUES_OFFSET
-            + STARS_OFFSET
-            + PRS_OFFSET
-            + FOLLOWERS_OFFSET
-            + REPO_OFFSET;
-
-        const RANK_S_VALUE: u8 = 1;
-        const RANK_DOUBLE_A_VALUE: u8 = 25;
-        const RANK_A2_VALUE: u8 = 45;
-        const RANK_A3_VALUE: u8 = 60;
-        const RANK_B_VALUE: u8 = 100;
-
-        const TOTAL_VALUES: u8 =
-            RANK_S_VALUE + RANK_DOUBLE_A_VALUE + RANK_A2_VALUE + RANK_A3_VALUE + RANK_B_VALUE;
-
-        let score = (self.commits as f64 * COMMITS_OFFSET
-            + self.contribs as f64 * CONTRIBS_OFFSET
-            + self.issues as f64 * ISSUES_OFFSET
-            + self.stars as f64 * STARS_OFFSET
-            + self.prs as f64 * PRS_OFFSET
-            + self.followers as f64 * FOLLOWERS_OFFSET
-            + self.repos as f64 * REPO_OFFSET)
-            / 100.;
-        let normalized_score =
-            (normalcdf(score, TOTAL_VALUES as f64, ALL_OFFSETS) * 100.).round() as u8;
-
-        let level = match normalized_score {
-            n if n < RANK_S_VALUE => "S+",
-            n if n < RANK_DOUBLE_A_VALUE => "S",
-            n if n < RANK_A2_VALUE => "A++",
-            n if n < RANK_A3_VALUE => "A+",
-            _ => "B+",
+    /// rank among all github users by `model`, the lower the score the better
+    pub fn calculate_rank(&self, config: &RankConfig, model: RankModel) -> Rank {
+        let (score, levels) = match model {
+            RankModel::Classic => (
+                self.classic_score(&config.classic_weights, config.classic_spread),
+                &config.classic_levels,
+            ),
+            RankModel::Percentile => (
+                self.percentile(&config.percentile_weights, &config.percentile_medians),
+                &config.percentile_levels,
+            ),
         };
+        // thresholds are exclusive in the classic model, inclusive in the percentile one
+        let level = levels
+            .iter()
+            .find(|i| match model {
+                RankModel::Classic => score < i.threshold,
+                RankModel::Percentile => score <= i.threshold,
+            })
+            .or(levels.last())
+            .map(|i| i.name.clone())
+            .unwrap_or_default();
 
         Rank {
-            level: level.to_owned(),
-            score: normalized_score,
+            level,
+            score,
+            percentile: (model == RankModel::Percentile).then_some(score),
+            model,
         }
     }
 
-    pub fn update_rank(&mut self) {
-        self.rank = self.calculate_rank()
+    /// normal cdf of the weighted stats, 0 to 100 but not a share of users,
+    /// no stats at all score 51
+    fn classic_score(&self, weights: &StatWeights, spread: f64) -> f64 {
+        // commits are left out of the offsets, as the original algorithm does
+        let all_offsets = weights.sum() - weights.commits;
+
+        let score = (self.commits as f64 * weights.commits
+            + self.contribs as f64 * weights.contribs
+            + self.issues as f64 * weights.issues
+            + self.stars as f64 * weights.stars
+            + self.prs as f64 * weights.prs
+            + self.followers as f64 * weights.followers
+            + self.repos as f64 * weights.repos)
+            / 100.;
+        (normalcdf(score, spread, all_offsets) * 100.).round()
+    }
+
+    fn percentile(&self, weights: &StatWeights, medians: &StatWeights) -> f64 {
+        let total_weight = weights.sum();
+        if total_weight <= 0. {
+            return 100.;
+        }
+        let exponential_cdf = |x: f64| 1. - 2_f64.powf(-x);
+        let log_normal_cdf = |x: f64| x / (1. + x);
+        let value = |stat: i64, median: f64| stat.max(0) as f64 / median;
+
+        let score = weights.commits * exponential_cdf(value(self.commits, medians.commits))
+            + weights.prs * exponential_cdf(value(self.prs, medians.prs))
+            + weights.issues * exponential_cdf(value(self.issues, medians.issues))
+            + weights.contribs * exponential_cdf(value(self.contribs, medians.contribs))
+            + weights.repos * exponential_cdf(value(self.repos, medians.repos))
+            + weights.stars * log_normal_cdf(value(self.stars, medians.stars))
+            + weights.followers * log_normal_cdf(value(self.followers, medians.followers));
+        (1. - score / total_weight) * 100.
+    }
+
+    /// count private contributions as commits, `include_private`
+    pub fn with_private(mut self) -> Self {
+        self.commits += self.private_contribs;
+        self
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Decode, Encode)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Rank {
     pub level: String,
-    pub score: u8,
-}
-
-impl Default for Rank {
-    fn default() -> Self {
-        Self {
-            level: "C".to_string(),
-            score: 0,
-        }
-    }
+    /// 0 to 100, the lower the better, the levels are given by it
+    pub score: f64,
+    /// top percent of users, only the percentile model has one
+    pub percentile: Option<f64>,
+    pub model: RankModel,
 }
 
 fn normalcdf(mean: f64, sigma: f64, to: f64) -> f64 {
@@ -116,10 +133,14 @@ fn normalcdf(mean: f64, sigma: f64, to: f64) -> f64 {
     (1. / 2.) * (1. + z.signum() * erf)
 }
 
-async fn fetch_total_stars(token: &str, user: &str, repo_to_hide: Vec<String>) -> i64 {
-    let client = build_client(token).unwrap();
-
-    let mut nodes = vec![];
+/// sum up stars of `user`'s repos except `exclude_repo`,
+/// repos come sorted by stars so paging stops at the first one without
+async fn fetch_total_stars(
+    client: &GithubClient,
+    user: &str,
+    exclude_repo: &[String],
+) -> Result<i64, AppError> {
+    let mut stars = 0;
     let mut has_next_page = true;
     let mut end_cursor = None;
     while has_next_page {
@@ -127,42 +148,43 @@ async fn fetch_total_stars(token: &str, user: &str, repo_to_hide: Vec<String>) -
             login: user.to_string(),
             after: end_cursor,
         };
-        let res = query_user_repos(&client, variables).await.unwrap();
-        let repos = res.user.unwrap().repositories;
-
-        if let Some(inner_nodes) = repos.nodes {
-            for inner_node in inner_nodes {
-                if inner_node.is_some() {
-                    let real_node = inner_node.unwrap();
-                    if real_node.stargazers.total_count as usize > 0 {
-                        nodes.push(real_node)
-                    }
-                }
+        let res = query_user_repos(client, variables).await?;
+        let repos = res.user.ok_or(UserRepoError::NotFound)?.repositories;
+
+        let nodes = repos.nodes.unwrap_or_default();
+        let mut reach_zero = false;
+        for node in nodes.into_iter().flatten() {
+            if node.stargazers.total_count <= 0 {
+                reach_zero = true;
+                break;
+            }
+            if !exclude_repo.contains(&node.name) {
+                stars += node.stargazers.total_count;
             }
         }
-        has_next_page = repos.page_info.has_next_page;
+        has_next_page = repos.page_info.has_next_page && !reach_zero;
         end_cursor = repos.page_info.end_cursor;
     }
 
-    nodes
-        .iter()
-        .filter(|i| !repo_to_hide.contains(&i.name))
-        .map(|i| i.stargazers.total_count)
-        .sum()
+    Ok(stars)
 }
 
-pub async fn get_user_github_stats(token: &str, username: &str) -> UserGithubStats {
-    let client = build_client(token).unwrap();
+/// stats of `username`, stars of `exclude_repo` are not counted
+pub async fn get_user_github_stats(
+    client: &GithubClient,
+    username: &str,
+    exclude_repo: &[String],
+) -> Result<UserGithubStats, AppError> {
     let variables = user_info::Variables {
         login: username.to_string(),
     };
-    let data = query_user_info(&client, variables).await.unwrap();
-    let user = data.user.unwrap();
+    let data = query_user_info(client, variables).await?;
+    let user = data.user.ok_or(UserRepoError::NotFound)?;
 
-    let stars = fetch_total_stars(token, username, vec![]).await;
+    let stars = fetch_total_stars(client, username, exclude_repo).await?;
     trace!("total_stars: {}", stars);
 
-    let mut stats = UserGithubStats {
+    let stats = UserGithubStats {
         login: user.login.clone(),
         name: user.name.unwrap_or(user.login.clone()),
         stars,
@@ -171,21 +193,59 @@ pub async fn get_user_github_stats(token: &str, username: &str) -> UserGithubSta
         prs: user.pull_requests.total_count,
         issues: user.open_issues.total_count + user.closed_issues.total_count,
         contribs: user.repositories_contributed_to.total_count,
+        private_contribs: user.contributions_collection.restricted_contributions_count,
         followers: user.followers.total_count,
-        rank: Rank::default(),
         __create_at: SystemTimeWrapper::default(),
     };
-    stats.update_rank();
-    stats
+    Ok(stats)
+}
+
+pub async fn query_user_info(
+    client: &GithubClient,
+    variables: user_info::Variables,
+) -> Result<user_info::ResponseData, AppError> {
+    client.query::<user_info::UserInfo>(variables).await
+}
+
+pub async fn query_user_repos(
+    client: &GithubClient,
+    variables: user_repos::Variables,
+) -> Result<user_repos::ResponseData, AppError> {
+    client.query::<user_repos::UserRepo>(variables).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::RankLevel;
+
+    fn stats(commits: i64, prs: i64, issues: i64, stars: i64, followers: i64) -> UserGithubStats {
+        UserGithubStats {
+            commits,
+            prs,
+            issues,
+            stars,
+            followers,
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn test_calculate_rank() {
-        let stats = UserGithubStats {
+        let config = RankConfig::default();
+        let stars_only = RankConfig {
+            percentile_weights: StatWeights {
+                commits: 0.,
+                prs: 0.,
+                issues: 0.,
+                stars: 1.,
+                followers: 0.,
+                contribs: 0.,
+                repos: 0.,
+            },
+            ..Default::default()
+        };
+        let classic = UserGithubStats {
             commits: 100,
             repos: 5,
             followers: 100,
@@ -195,35 +255,73 @@ mod tests {
             issues: 200,
             ..Default::default()
         };
-        let rank = stats.calculate_rank();
-        assert_eq!(
-            rank,
-            Rank {
-                level: "A+".to_string(),
-                score: 49
-            }
-        );
-    }
-}
-
-pub async fn query_user_info(
-    client: &Client,
-    variables: user_info::Variables,
-) -> Result<user_info::ResponseData> {
-    let request_body = user_info::UserInfo::build_query(variables);
-    let res = client.post(GITHUB_API).json(&request_body).send().await?;
-    let response_body: Response<user_info::ResponseData> = res.json().await?;
-    trace!("{:#?}", response_body);
-    Ok(response_body.data.unwrap())
-}
+        let cases = [
+            (&config, RankModel::Classic, classic.clone(), "A+", 49.),
+            (&config, RankModel::Classic, stats(0, 0, 0, 0, 0), "A+", 51.),
+            (
+                &config,
+                RankModel::Classic,
+                stats(30000, 2000, 2000, 90000, 30000),
+                "S+",
+                0.,
+            ),
+            (
+                &config,
+                RankModel::Percentile,
+                stats(0, 0, 0, 0, 0),
+                "C",
+                100.,
+            ),
+            // every stat at its median
+            (
+                &config,
+                RankModel::Percentile,
+                stats(250, 50, 25, 50, 10),
+                "B+",
+                50.,
+            ),
+            (
+                &config,
+                RankModel::Percentile,
+                stats(1000, 300, 100, 1000, 500),
+                "A+",
+                4.04,
+            ),
+            (&config, RankModel::Percentile, classic, "A", 19.11),
+            (
+                &stars_only,
+                RankModel::Percentile,
+                stats(0, 0, 0, 150, 0),
+                "A",
+                25.,
+            ),
+        ];
+        for (config, model, stats, level, score) in cases {
+            let rank = stats.calculate_rank(config, model);
+            assert_eq!(rank.level, level, "{stats:?} {model:?}");
+            assert!(
+                (rank.score - score).abs() < 0.01,
+                "{stats:?} {model:?}: {} != {score}",
+                rank.score
+            );
+            assert_eq!(
+                rank.percentile.is_some(),
+                model == RankModel::Percentile,
+                "{stats:?} {model:?}"
+            );
+        }
 
-pub async fn query_user_repos(
-    client: &Client,
-    variables: user_repos::Variables,
-) -> Result<user_repos::ResponseData> {
-    let request_body = user_repos::UserRepo::build_query(variables);
-    let res = client.post(GITHUB_API).json(&request_body).send().await?;
-    let response_body: Response<user_repos::ResponseData> = res.json().await?;
-    trace!("{:#?}", response_body);
-    Ok(response_body.data.unwrap())
+        // editing the level thresholds leaves the score alone
+        let levels = RankConfig {
+            classic_levels: [("A", 50.), ("B", 100.)]
+                .map(|(name, threshold)| RankLevel {
+                    name: name.to_string(),
+                    threshold,
+                })
+                .to_vec(),
+            ..Default::default()
+        };
+        let rank = stats(0, 0, 0, 0, 0).calculate_rank(&levels, RankModel::Classic);
+        assert_eq!((rank.level.as_str(), rank.score), ("B", 51.));
+    }
 }
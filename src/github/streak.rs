@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+
+use bincode::{Decode, Encode};
+use chrono::{Duration, NaiveDate};
+use futures_util::future::try_join_all;
+use serde::Serialize;
+
+use super::{gen::user_streak, GithubClient};
+use crate::{
+    error::{AppError, UserRepoError},
+    utils::{today, MonitorTime, SystemTimeWrapper},
+};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub async fn query_user_streak(
+    client: &GithubClient,
+    variables: user_streak::Variables,
+) -> Result<user_streak::ResponseData, AppError> {
+    client.query::<user_streak::UserStreak>(variables).await
+}
+
+/// consecutive days with contributions, dates in `YYYY-MM-DD`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Decode, Encode, Serialize)]
+pub struct Streak {
+    pub start: String,
+    pub end: String,
+    pub length: u32,
+}
+
+#[derive(Debug, Clone, Default, Decode, Encode, Serialize)]
+pub struct UserStreak {
+    pub login: String,
+    pub name: String,
+    pub total_contributions: i64,
+    /// first day of the contribution calendar
+    pub first_contribution: String,
+    pub current: Streak,
+    pub longest: Streak,
+    #[serde(skip)]
+    pub(crate) __create_at: SystemTimeWrapper,
+}
+
+impl MonitorTime for UserStreak {
+    fn create_at(&self) -> SystemTimeWrapper {
+        self.__create_at
+    }
+}
+
+/// calculate current and longest streak from ordered daily contributions,
+/// today does not break the current streak before it is over
+fn calculate_streaks(days: &[(NaiveDate, i64)], today: NaiveDate) -> (Streak, Streak) {
+    let mut current = Streak::default();
+    let mut longest = Streak::default();
+    let mut start: Option<NaiveDate> = None;
+    let mut last_day: Option<NaiveDate> = None;
+
+    for &(date, count) in days.iter().filter(|(date, _)| *date <= today) {
+        if count > 0 {
+            let streak_start = match (start, last_day) {
+                (Some(s), Some(last)) if last + Duration::days(1) == date => s,
+                _ => date,
+            };
+            start = Some(streak_start);
+            last_day = Some(date);
+            current = Streak {
+                start: streak_start.format(DATE_FORMAT).to_string(),
+                end: date.format(DATE_FORMAT).to_string(),
+                length: (date - streak_start).num_days() as u32 + 1,
+            };
+            if current.length > longest.length {
+                longest = current.clone();
+            }
+        } else if date != today {
+            start = None;
+            current = Streak::default();
+        }
+    }
+
+    (current, longest)
+}
+
+pub async fn get_user_streak(
+    client: &GithubClient,
+    username: &str,
+) -> Result<UserStreak, AppError> {
+    let variables = user_streak::Variables {
+        login: username.to_string(),
+        from: None,
+        to: None,
+        with_calendar: false,
+    };
+    let data = query_user_streak(client, variables).await?;
+    let user = data.user.ok_or(UserRepoError::NotFound)?;
+
+    // a calendar covers a year at most, fetch all years at once for all time streaks
+    let calendars = try_join_all(user.contributions_collection.contribution_years.iter().map(
+        |year| async move {
+            let variables = user_streak::Variables {
+                login: username.to_string(),
+                from: Some(format!("{year}-01-01T00:00:00Z")),
+                to: Some(format!("{year}-12-31T23:59:59Z")),
+                with_calendar: true,
+            };
+            let data = query_user_streak(client, variables).await?;
+            let user = data.user.ok_or(UserRepoError::NotFound)?;
+            Ok::<_, AppError>(user.contributions_collection.contribution_calendar)
+        },
+    ))
+    .await?;
+    let mut days: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for calendar in calendars.into_iter().flatten() {
+        for day in calendar.weeks.into_iter().flat_map(|i| i.contribution_days) {
+            if let Ok(date) = NaiveDate::parse_from_str(&day.date, DATE_FORMAT) {
+                days.insert(date, day.contribution_count);
+            }
+        }
+    }
+
+    let today = today();
+    let days: Vec<(NaiveDate, i64)> = days.into_iter().filter(|(d, _)| *d <= today).collect();
+    let (current, longest) = calculate_streaks(&days, today);
+    let first_contribution = days
+        .iter()
+        .find(|(_, count)| *count > 0)
+        .map(|(date, _)| date.format(DATE_FORMAT).to_string())
+        .unwrap_or_default();
+
+    Ok(UserStreak {
+        login: user.login.clone(),
+        name: user.name.unwrap_or(user.login),
+        total_contributions: days.iter().map(|(_, count)| count).sum(),
+        first_contribution,
+        current,
+        longest,
+        __create_at: SystemTimeWrapper::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(start: &str, counts: &[i64]) -> Vec<(NaiveDate, i64)> {
+        let start = NaiveDate::parse_from_str(start, DATE_FORMAT).unwrap();
+        counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (start + Duration::days(i as i64), *count))
+            .collect()
+    }
+
+    #[test]
+    fn test_calculate_streaks() {
+        let today = NaiveDate::parse_from_str("2023-01-08", DATE_FORMAT).unwrap();
+
+        // today without contributions keeps the current streak
+        let (current, longest) =
+            calculate_streaks(&days("2023-01-01", &[1, 2, 3, 0, 1, 1, 1, 0]), today);
+        assert_eq!(
+            current,
+            Streak {
+                start: "2023-01-05".to_string(),
+                end: "2023-01-07".to_string(),
+                length: 3
+            }
+        );
+        assert_eq!(longest.start, "2023-01-01");
+        assert_eq!(longest.length, 3);
+
+        // a missing day yesterday breaks the current streak
+        let (current, longest) =
+            calculate_streaks(&days("2023-01-01", &[1, 1, 1, 1, 0, 0, 0, 0]), today);
+        assert_eq!(current, Streak::default());
+        assert_eq!(longest.end, "2023-01-04");
+        assert_eq!(longest.length, 4);
+    }
+}
@@ -1,35 +1,41 @@
 use std::collections::HashMap;
 
 use bincode::{Decode, Encode};
-use color_eyre::Result;
-use graphql_client::{GraphQLQuery, Response};
-use reqwest::Client;
-use tracing::trace;
+use serde::Serialize;
 
-use super::{build_client, gen::top_langs, GITHUB_API};
-use crate::utils::{MonitorTime, SystemTimeWrapper};
+use super::{gen::top_langs, GithubClient};
+use crate::{
+    error::{AppError, UserRepoError},
+    utils::{MonitorTime, SystemTimeWrapper},
+};
 
 pub async fn query_top_langs(
-    client: &Client,
+    client: &GithubClient,
     variables: top_langs::Variables,
-) -> Result<top_langs::ResponseData> {
-    let request_body = top_langs::TopLang::build_query(variables);
-    let res = client.post(GITHUB_API).json(&request_body).send().await?;
-    let response_body: Response<top_langs::ResponseData> = res.json().await?;
-    trace!("{:#?}", response_body);
-    Ok(response_body.data.unwrap())
+) -> Result<top_langs::ResponseData, AppError> {
+    client.query::<top_langs::TopLang>(variables).await
 }
 
-#[derive(Debug, Clone, Decode, Encode)]
+#[derive(Debug, Clone, Decode, Encode, Serialize)]
 pub struct Lang {
     pub name: String,
     pub color: Option<String>,
     pub size: usize,
+    /// number of repos using this language
+    pub count: usize,
 }
 
-#[derive(Debug, Clone, Default, Decode, Encode)]
+/// languages used by one repository
+#[derive(Debug, Clone, Decode, Encode, Serialize)]
+pub struct RepoLangs {
+    pub name: String,
+    pub langs: Vec<Lang>,
+}
+
+#[derive(Debug, Clone, Default, Decode, Encode, Serialize)]
 pub struct TopLangs {
-    pub langs: HashMap<String, Lang>,
+    pub repos: Vec<RepoLangs>,
+    #[serde(skip)]
     pub(crate) __create_at: SystemTimeWrapper,
 }
 
@@ -39,39 +45,59 @@ impl MonitorTime for TopLangs {
     }
 }
 
-pub async fn get_top_langs(token: &str, username: &str) -> TopLangs {
-    let mut langs_map = HashMap::new();
-    let client = build_client(token).unwrap();
+impl TopLangs {
+    /// sum up languages of all repos, except repos in `exclude_repo`
+    pub fn langs(&self, exclude_repo: &[String]) -> HashMap<String, Lang> {
+        let mut langs_map: HashMap<String, Lang> = HashMap::new();
+        for repo in self
+            .repos
+            .iter()
+            .filter(|repo| !exclude_repo.contains(&repo.name))
+        {
+            for lang in &repo.langs {
+                langs_map
+                    .entry(lang.name.clone())
+                    .and_modify(|origin| {
+                        origin.size += lang.size;
+                        origin.count += lang.count;
+                    })
+                    .or_insert(lang.clone());
+            }
+        }
+        langs_map
+    }
+}
+
+pub async fn get_top_langs(client: &GithubClient, username: &str) -> Result<TopLangs, AppError> {
     let variables = top_langs::Variables {
         login: username.to_string(),
     };
-    let data = query_top_langs(&client, variables).await.unwrap();
-    let nodes = data.user.unwrap().repositories.nodes;
-    if let Some(inner_nodes) = nodes {
-        for lang in inner_nodes.into_iter().flatten() {
-            if let Some(inner_inner_lang) = lang.languages {
-                if let Some(edges) = inner_inner_lang.edges {
-                    for edge in edges.into_iter().flatten() {
-                        let name = edge.node.name.to_string();
-                        let item = Lang {
-                            name: edge.node.name.to_string(),
-                            color: edge.node.color,
-                            size: edge.size as usize,
-                        };
-                        if langs_map.get(&name).is_none() {
-                            langs_map.insert(name, item);
-                        } else {
-                            let origin = langs_map.get_mut(&name).unwrap();
-                            origin.size += item.size;
-                        }
-                    }
-                }
-            }
-        }
+    let data = query_top_langs(client, variables).await?;
+    let nodes = data.user.ok_or(UserRepoError::NotFound)?.repositories.nodes;
+
+    let mut repos = vec![];
+    for repo in nodes.unwrap_or_default().into_iter().flatten() {
+        let langs = repo
+            .languages
+            .and_then(|i| i.edges)
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .map(|edge| Lang {
+                name: edge.node.name,
+                color: edge.node.color,
+                size: edge.size as usize,
+                count: 1,
+            })
+            .collect();
+        repos.push(RepoLangs {
+            name: repo.name,
+            langs,
+        });
     }
 
-    TopLangs {
-        langs: langs_map,
+    Ok(TopLangs {
+        repos,
         __create_at: SystemTimeWrapper::default(),
-    }
+    })
 }
@@ -377,3 +377,57 @@ impl Humanize for SystemTime {
         HumanTime::from(*self).to_string()
     }
 }
+
+/// Parse durations like `90s`, `30m`, `4h`, `1d`, `2w` or `1h30m`,
+/// a bare number is taken as seconds
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if let Ok(secs) = text.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let mut total = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => S_MINUTE,
+            'h' => S_HOUR,
+            'd' => S_DAY,
+            'w' => S_WEEK,
+            _ => return None,
+        };
+        total += number.parse::<u64>().ok()?.checked_mul(unit)?;
+        number.clear();
+    }
+    if !number.is_empty() || text.is_empty() {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(
+            parse_duration("30m"),
+            Some(Duration::from_secs(30 * S_MINUTE))
+        );
+        assert_eq!(parse_duration("1d"), Some(Duration::from_secs(S_DAY)));
+        assert_eq!(
+            parse_duration("1h30m"),
+            Some(Duration::from_secs(S_HOUR + 30 * S_MINUTE))
+        );
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("4 hours"), None);
+    }
+}
@@ -5,7 +5,7 @@ use color_eyre::Result;
 use serde::Serialize;
 use tokio::process::Command;
 
-use crate::humantime::HumanTime;
+use crate::{github::TokenBudget, humantime::HumanTime};
 
 #[derive(Debug, Default, Serialize)]
 pub struct PkgInfo {
@@ -21,6 +21,8 @@ pub struct Status {
     sysinfo: MySysInfo,
     services: Vec<Service>,
     pkginfo: PkgInfo,
+    /// rate limit of each github token
+    github: Vec<TokenBudget>,
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -93,7 +95,7 @@ impl MySysInfo {
 }
 
 impl Status {
-    pub async fn init(service_names: Vec<String>) -> Self {
+    pub async fn init(service_names: Vec<String>, github: Vec<TokenBudget>) -> Self {
         let utsname = MyUtsName::init().unwrap_or_default();
         let sysinfo = MySysInfo::init().unwrap_or_default();
 
@@ -112,6 +114,7 @@ impl Status {
             sysinfo,
             services,
             pkginfo,
+            github,
         }
     }
 }
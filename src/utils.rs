@@ -1,7 +1,8 @@
 use std::{ops::Deref, time::SystemTime};
 
 use bincode::{Decode, Encode};
-use chrono::{Datelike, NaiveDateTime};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer};
 
 /// use for cache
 #[derive(Debug, Copy, Clone, Decode, Encode)]
@@ -26,10 +27,68 @@ pub trait MonitorTime {
 }
 
 #[inline]
-pub fn current_year() -> i32 {
+pub fn today() -> NaiveDate {
     let d = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
     let date = NaiveDateTime::from_timestamp_millis(d.as_millis() as i64).unwrap();
-    date.year()
+    date.date()
+}
+
+#[inline]
+pub fn current_year() -> i32 {
+    today().year()
+}
+
+/// format time for http headers like `Expires`, `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// parse http header dates written by `http_date`
+pub fn parse_http_date(input: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(input)
+        .ok()
+        .map(|i| SystemTime::from(i.with_timezone(&Utc)))
+}
+
+/// escape text before putting it into svg
+pub fn encode_html(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// deserialize query value like `a,b,c` into a list, empty items are skipped
+pub fn deserialize_comma_separated<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = String::deserialize(deserializer)?;
+    Ok(input
+        .split(',')
+        .map(|i| i.trim())
+        .filter(|i| !i.is_empty())
+        .map(|i| i.to_string())
+        .collect())
+}
+
+/// deserialize an optional number, invalid input is ignored as if missing
+pub fn deserialize_lenient_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = Option::<String>::deserialize(deserializer)?;
+    Ok(input.and_then(|i| i.trim().parse().ok()))
 }
@@ -0,0 +1,480 @@
+//! card flows against a local mock of the github graphql api
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::post,
+    Router,
+};
+use mine_stats::{
+    api,
+    config::{Config, Themes},
+};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+const TOKEN: &str = "mock-token";
+const ADMIN_TOKEN: &str = "admin-token";
+
+#[derive(Default)]
+struct Mock {
+    user_info_calls: AtomicUsize,
+    user_repo_calls: AtomicUsize,
+    user_streak_calls: AtomicUsize,
+}
+
+async fn graphql(
+    State(mock): State<Arc<Mock>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if headers.get("authorization").and_then(|i| i.to_str().ok())
+        != Some(&format!("Bearer {TOKEN}"))
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let login = body["variables"]["login"].as_str().unwrap_or_default();
+    if login == "ghost" {
+        return Json(json!({
+            "data": { "user": null },
+            "errors": [{ "message": "Could not resolve to a User with the login of 'ghost'." }],
+        }))
+        .into_response();
+    }
+
+    let mut data = match body["operationName"].as_str().unwrap_or_default() {
+        "UserInfo" => {
+            // the first call fails like a flaky github, it is retried
+            if mock.user_info_calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return StatusCode::BAD_GATEWAY.into_response();
+            }
+            json!({
+                "user": {
+                    "name": "Light",
+                    "login": login,
+                    "contributionsCollection": {
+                        "totalCommitContributions": 120,
+                        "restrictedContributionsCount": 30,
+                    },
+                    "repositoriesContributedTo": { "totalCount": 8 },
+                    "pullRequests": { "totalCount": 15 },
+                    "openIssues": { "totalCount": 3 },
+                    "closedIssues": { "totalCount": 4 },
+                    "followers": { "totalCount": 42 },
+                    "repositories": { "totalCount": 4 },
+                }
+            })
+        }
+        "UserRepo" => {
+            mock.user_repo_calls.fetch_add(1, Ordering::SeqCst);
+            // sorted by stars, there is no need to fetch page 3
+            let (nodes, end_cursor) = match body["variables"]["after"].as_str() {
+                None => (json!([stars("a", 10), stars("b", 5)]), "page-2"),
+                Some("page-2") => (json!([stars("c", 2), stars("d", 0)]), "page-3"),
+                Some(_) => return StatusCode::BAD_REQUEST.into_response(),
+            };
+            json!({
+                "user": {
+                    "repositories": {
+                        "nodes": nodes,
+                        "pageInfo": { "hasNextPage": true, "endCursor": end_cursor },
+                    }
+                }
+            })
+        }
+        "TopLang" => json!({
+            "user": {
+                "repositories": {
+                    "nodes": [
+                        { "name": "a", "languages": { "edges": [
+                            lang("Rust", Some("#dea584"), 300),
+                            lang("Shell", None, 20),
+                        ] } },
+                        { "name": "b", "languages": { "edges": [lang("Go", Some("#00ADD8"), 80)] } },
+                        { "name": "c", "languages": null },
+                    ]
+                }
+            }
+        }),
+        "UserStreak" => {
+            mock.user_streak_calls.fetch_add(1, Ordering::SeqCst);
+            let variables = &body["variables"];
+            // the years, then a calendar per year
+            let calendar = match (
+                variables["withCalendar"].as_bool(),
+                variables["from"].as_str(),
+            ) {
+                (Some(false), None) => None,
+                (Some(true), Some("2022-01-01T00:00:00Z")) => Some(days(&[
+                    ("2022-12-29", 0),
+                    ("2022-12-30", 1),
+                    ("2022-12-31", 2),
+                ])),
+                (Some(true), Some("2023-01-01T00:00:00Z")) => Some(days(&[
+                    ("2023-01-01", 3),
+                    ("2023-01-02", 1),
+                    ("2023-01-03", 0),
+                    ("2023-01-04", 2),
+                    ("2023-01-05", 0),
+                ])),
+                _ => return StatusCode::BAD_REQUEST.into_response(),
+            };
+            let mut collection = json!({ "contributionYears": [2023, 2022] });
+            if let Some(calendar) = calendar {
+                collection["contributionCalendar"] = calendar;
+            }
+            json!({
+                "user": {
+                    "name": "Light",
+                    "login": login,
+                    "contributionsCollection": collection,
+                }
+            })
+        }
+        "Repo" => {
+            let repo = body["variables"]["repo"].as_str().unwrap_or_default();
+            json!({
+                "repositoryOwner": {
+                    "repository": {
+                        "name": repo,
+                        "nameWithOwner": format!("{login}/{repo}"),
+                        "description": "a repo",
+                        "isArchived": false,
+                        "isTemplate": false,
+                        "stargazers": { "totalCount": 10 },
+                        "forkCount": 2,
+                        "primaryLanguage": { "color": "#dea584", "name": "Rust" },
+                    }
+                }
+            })
+        }
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    data["rateLimit"] =
+        json!({ "limit": 5000, "remaining": 4999, "resetAt": "2030-01-01T00:00:00Z" });
+    Json(json!({ "data": data })).into_response()
+}
+
+fn stars(name: &str, count: i64) -> Value {
+    json!({ "name": name, "stargazers": { "totalCount": count } })
+}
+
+/// a contribution calendar of one week
+fn days(days: &[(&str, i64)]) -> Value {
+    let days: Vec<_> = days
+        .iter()
+        .map(|(date, count)| json!({ "date": date, "contributionCount": count }))
+        .collect();
+    json!({ "weeks": [{ "contributionDays": days }] })
+}
+
+fn lang(name: &str, color: Option<&str>, size: i64) -> Value {
+    json!({ "size": size, "node": { "name": name, "color": color } })
+}
+
+async fn serve(app: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap()
+    });
+    addr
+}
+
+/// start the mock github and the service using it, return the service address
+async fn setup() -> (SocketAddr, Arc<Mock>) {
+    let mock = Arc::new(Mock::default());
+    let github = serve(
+        Router::new()
+            .route("/api/graphql", post(graphql))
+            .with_state(mock.clone()),
+    )
+    .await;
+    let config: Config = format!(
+        r#"
+        github_api_token "{TOKEN}"
+        allow_users "light4" "ghost"
+        github {{
+            api_url "http://{github}/api/graphql"
+        }}
+        admin {{
+            token "{ADMIN_TOKEN}"
+        }}
+        "#
+    )
+    .parse()
+    .unwrap();
+    let app = serve(api::app(config, <Themes as Default>::default())).await;
+    (app, mock)
+}
+
+async fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+    let res = reqwest::get(format!("http://{addr}{path}")).await.unwrap();
+    (res.status().as_u16(), res.text().await.unwrap())
+}
+
+/// text of the element with `data-testid`
+fn test_id<'a>(svg: &'a str, id: &str) -> &'a str {
+    let start = svg.find(&format!(r#"data-testid="{id}""#)).unwrap();
+    let text = &svg[start..];
+    let text = &text[text.find('>').unwrap() + 1..];
+    text[..text.find('<').unwrap()].trim()
+}
+
+#[tokio::test]
+async fn test_stats_card() {
+    let (addr, mock) = setup().await;
+
+    let (status, svg) = get(addr, "/stats?user=light4").await;
+    assert_eq!(status, 200, "{svg}");
+    // stars of both pages
+    assert_eq!(test_id(&svg, "star"), "17");
+    assert_eq!(test_id(&svg, "commits"), "120");
+    assert_eq!(test_id(&svg, "prs"), "15");
+    assert_eq!(test_id(&svg, "issues"), "7");
+    assert_eq!(test_id(&svg, "contribs"), "8");
+    assert_eq!(mock.user_info_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(mock.user_repo_calls.load(Ordering::SeqCst), 2);
+
+    // cached, private contributions are added on render
+    let (status, svg) = get(addr, "/stats?user=light4&include_private=true").await;
+    assert_eq!(status, 200);
+    assert_eq!(test_id(&svg, "commits"), "150");
+    assert_eq!(mock.user_info_calls.load(Ordering::SeqCst), 2);
+
+    // cached apart from the unfiltered stats
+    let (status, svg) = get(addr, "/stats?user=light4&exclude_repo=a,c").await;
+    assert_eq!(status, 200);
+    assert_eq!(test_id(&svg, "star"), "5");
+    assert_eq!(mock.user_repo_calls.load(Ordering::SeqCst), 4);
+    let (_, svg) = get(addr, "/stats?user=light4&exclude_repo=c,a").await;
+    assert_eq!(test_id(&svg, "star"), "5");
+    assert_eq!(mock.user_repo_calls.load(Ordering::SeqCst), 4);
+
+    let (status, _) = get(addr, "/stats?user=ghost").await;
+    assert_eq!(status, 404);
+
+    // long titles grow the card, or are cut to fit `card_width`
+    let title = "A%20Very%20Long%20Title%20For%20The%20GitHub%20Stats%20Of%20Light%20And%20Friends";
+    let (_, svg) = get(addr, &format!("/stats?user=light4&custom_title={title}")).await;
+    assert!(!svg.contains(r#"width="495""#), "{svg}");
+    assert!(!test_id(&svg, "header").ends_with('…'));
+    let (_, svg) = get(
+        addr,
+        &format!("/stats?user=light4&custom_title={title}&card_width=450"),
+    )
+    .await;
+    assert!(test_id(&svg, "header").ends_with('…'), "{svg}");
+
+    // the rank shares the cached stats
+    let calls = mock.user_info_calls.load(Ordering::SeqCst);
+    let (status, body) = get(addr, "/api/v1/rank?user=light4&rank_model=percentile").await;
+    assert_eq!(status, 200, "{body}");
+    let rank: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(rank["model"], "percentile");
+    assert!((0.0..=100.0).contains(&rank["percentile"].as_f64().unwrap()));
+    assert_eq!(mock.user_info_calls.load(Ordering::SeqCst), calls);
+    // and the stats without excluded repos
+    let (status, _) = get(addr, "/api/v1/rank?user=light4&exclude_repo=c,a").await;
+    assert_eq!(status, 200);
+    assert_eq!(mock.user_info_calls.load(Ordering::SeqCst), calls);
+    assert_eq!(mock.user_repo_calls.load(Ordering::SeqCst), 4);
+    let (status, body) = get(addr, "/api/v1/rank?user=ghost").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("hint"), "{body}");
+}
+
+#[tokio::test]
+async fn test_top_langs_card() {
+    let (addr, _) = setup().await;
+
+    let (status, svg) = get(addr, "/stats/top-langs?user=light4").await;
+    assert_eq!(status, 200, "{svg}");
+    let names = svg
+        .match_indices(r#"data-testid="lang-name""#)
+        .map(|(i, _)| test_id(&svg[i..], "lang-name"))
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Rust", "Go", "Shell"]);
+    assert!(svg.contains("75.00%"), "{svg}");
+
+    let (status, svg) = get(addr, "/stats/top-langs?user=light4&locale=de-DE").await;
+    assert_eq!(status, 200);
+    assert!(svg.contains("75,00%"), "{svg}");
+
+    let (status, svg) = get(addr, "/stats/top-langs?user=light4&exclude_repo=a").await;
+    assert_eq!(status, 200);
+    assert!(!svg.contains("Rust"));
+    assert!(svg.contains("Go"));
+
+    let (status, _) = get(addr, "/stats/top-langs?user=ghost").await;
+    assert_eq!(status, 404);
+
+    // shared parameters that can not be parsed are shown on an error card
+    for path in [
+        "/stats/top-langs?user=light4&format=gif",
+        "/stats/streak?user=light4&scale=big",
+        "/stats/pin?user=light4&repo=a&number_format=tiny",
+    ] {
+        let (status, svg) = get(addr, path).await;
+        assert_eq!(status, 400, "{path}");
+        assert!(svg.starts_with("<svg"), "{svg}");
+    }
+}
+
+#[tokio::test]
+async fn test_json_api() {
+    let (addr, mock) = setup().await;
+
+    let (status, body) = get(addr, "/api/v1/stats?user=light4&include_private=true").await;
+    assert_eq!(status, 200, "{body}");
+    let stats: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["data"]["login"], "light4");
+    assert_eq!(stats["data"]["stars"], 17);
+    assert_eq!(stats["data"]["commits"], 150);
+    assert!(stats["rank"]["level"].is_string());
+    assert!(stats["age"].as_u64().unwrap() < 60);
+
+    // shares the cache of the card
+    let (status, _) = get(addr, "/stats?user=light4").await;
+    assert_eq!(status, 200);
+    assert_eq!(mock.user_info_calls.load(Ordering::SeqCst), 2);
+
+    let (status, body) = get(addr, "/api/v1/top-langs?user=light4&hide=go").await;
+    assert_eq!(status, 200, "{body}");
+    let top_langs: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(top_langs["data"]["repos"].as_array().unwrap().len(), 3);
+    assert_eq!(top_langs["langs"][0]["name"], "Rust");
+    assert_eq!(top_langs["langs"].as_array().unwrap().len(), 2);
+
+    let (status, body) = get(addr, "/api/v1/stats?user=someone").await;
+    assert_eq!(status, 403);
+    assert!(body.starts_with('{'), "{body}");
+    let (status, _) = get(addr, "/api/v1/top-langs?user=ghost").await;
+    assert_eq!(status, 404);
+    let (status, body) = get(addr, "/api/v1/streak?user=light4&number_format=tiny").await;
+    assert_eq!(status, 400);
+    assert!(body.starts_with('{'), "{body}");
+
+    let (status, body) = get(addr, "/api/v1/pin?user=light4&repo=a").await;
+    assert_eq!(status, 200, "{body}");
+    let pin: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(pin["data"]["name_with_owner"], "light4/a");
+    assert_eq!(pin["data"]["stars"], 10);
+    let (status, _) = get(addr, "/api/v1/pin?user=light4&repo=a&number_format=tiny").await;
+    assert_eq!(status, 400);
+    let (status, body) = get(addr, "/api/v1/pin?user=light4").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("Missing repo parameter"), "{body}");
+    let (status, body) = get(addr, "/api/v1/pin?repo=a").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("Missing user parameter"), "{body}");
+}
+
+#[tokio::test]
+async fn test_png_card() {
+    let (addr, _) = setup().await;
+
+    for (path, width) in [
+        ("/stats?user=light4&format=png&scale=2", 990),
+        ("/stats/top-langs?user=light4&format=png", 300),
+    ] {
+        let res = reqwest::get(format!("http://{addr}{path}")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200, "{path}");
+        assert_eq!(res.headers()["content-type"], "image/png");
+        let png = res.bytes().await.unwrap();
+        assert!(png.starts_with(b"\x89PNG"), "{path}");
+        // width in the IHDR chunk
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), width);
+    }
+
+    // the same card is rasterised once, whatever else is in the query
+    for path in [
+        "/stats?user=light4&format=png&scale=2.0",
+        "/stats?user=light4&format=png&scale=2&utm_source=readme",
+    ] {
+        let (status, _) = get(addr, path).await;
+        assert_eq!(status, 200, "{path}");
+    }
+    let keys = reqwest::Client::new()
+        .get(format!("http://{addr}/cache/keys"))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(keys.matches("light4/png=").count(), 2, "{keys}");
+
+    let (status, body) = get(
+        addr,
+        "/stats?user=light4&format=png&scale=4&card_width=1000&line_height=100",
+    )
+    .await;
+    assert_eq!(status, 400);
+    assert!(body.contains("over the limit"), "{body}");
+}
+
+#[tokio::test]
+async fn test_admin_routes() {
+    let (addr, _) = setup().await;
+    let client = reqwest::Client::new();
+    let status = |path: &str, token: Option<&str>| {
+        let mut request = client.get(format!("http://{addr}{path}"));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        async move { request.send().await.unwrap().status().as_u16() }
+    };
+
+    // `admin { routes }` and the admin api
+    for path in ["/cache/keys", "/cache/entries", "/status"] {
+        assert_eq!(status(path, None).await, 401, "{path}");
+        assert_eq!(status(path, Some("wrong")).await, 401, "{path}");
+        assert_eq!(status(path, Some(ADMIN_TOKEN)).await, 200, "{path}");
+    }
+    // cards stay public
+    assert_eq!(status("/stats?user=light4", None).await, 200);
+    assert_eq!(status("/themes", None).await, 200);
+}
+
+#[tokio::test]
+async fn test_streak() {
+    let (addr, mock) = setup().await;
+
+    let (status, body) = get(addr, "/api/v1/streak?user=light4").await;
+    assert_eq!(status, 200, "{body}");
+    let streak: Value = serde_json::from_str(&body).unwrap();
+    // the longest streak crosses the new year, it takes both calendars
+    assert_eq!(
+        streak["data"]["longest"],
+        json!({ "start": "2022-12-30", "end": "2023-01-02", "length": 4 })
+    );
+    assert_eq!(streak["data"]["current"]["length"], 0);
+    assert_eq!(streak["data"]["total_contributions"], 9);
+    assert_eq!(streak["data"]["first_contribution"], "2022-12-30");
+    assert_eq!(mock.user_streak_calls.load(Ordering::SeqCst), 3);
+
+    // the card shares the cache
+    let (status, svg) = get(addr, "/stats/streak?user=light4").await;
+    assert_eq!(status, 200, "{svg}");
+    assert!(svg.contains("Dec 30, 2022"), "{svg}");
+    assert_eq!(mock.user_streak_calls.load(Ordering::SeqCst), 3);
+
+    let (status, _) = get(addr, "/stats/streak?user=ghost").await;
+    assert_eq!(status, 404);
+}